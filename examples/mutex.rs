@@ -0,0 +1,125 @@
+extern crate mcctl_rs;
+
+use mcctl_rs::{
+    formula::{Formula, Prop},
+    lts::Lts,
+    mark,
+    process::{ExecUnit, Label, Location, Process, Trans},
+    viz,
+};
+
+// Peterson's mutual exclusion algorithm for two processes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Vars {
+    flag: [bool; 2],
+    turn: usize,
+}
+
+impl Vars {
+    fn init() -> Self {
+        Vars {
+            flag: [false, false],
+            turn: 0,
+        }
+    }
+}
+
+use std::fmt;
+impl fmt::Display for Vars {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "flag0={} flag1={} turn={}",
+            self.flag[0], self.flag[1], self.turn
+        )
+    }
+}
+
+macro_rules! peterson {
+    ($me:expr, $other:expr) => {
+        vec![
+            ExecUnit {
+                src: Location::new("idle"),
+                invariant: vec![],
+                transs: vec![Trans {
+                    label: Label::new(concat!("flag", $me, "=1")),
+                    dst: Location::new("ready"),
+                    guard: |_| true,
+                    action: |mut vars| {
+                        vars.flag[$me] = true;
+                        vars
+                    },
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+            ExecUnit {
+                src: Location::new("ready"),
+                invariant: vec![],
+                transs: vec![Trans {
+                    label: Label::new(concat!("turn=", $other)),
+                    dst: Location::new("wait"),
+                    guard: |_| true,
+                    action: |mut vars| {
+                        vars.turn = $other;
+                        vars
+                    },
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+            ExecUnit {
+                src: Location::new("wait"),
+                invariant: vec![],
+                transs: vec![Trans {
+                    label: Label::new(concat!("enter", $me)),
+                    dst: Location::new("crit"),
+                    guard: |vars| !vars.flag[$other] || vars.turn == $me,
+                    action: |vars| vars,
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+            ExecUnit {
+                src: Location::new("crit"),
+                invariant: vec![],
+                transs: vec![Trans {
+                    label: Label::new(concat!("flag", $me, "=0")),
+                    dst: Location::new("idle"),
+                    guard: |_| true,
+                    action: |mut vars| {
+                        vars.flag[$me] = false;
+                        vars
+                    },
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+        ]
+    };
+}
+
+fn main() {
+    let process_p: Process<Vars> = peterson!(0, 1);
+    let process_q: Process<Vars> = peterson!(1, 0);
+
+    let processes = vec![process_p, process_q];
+    let mut lts = Lts::concurrent_composition(Vars::init(), processes).unwrap();
+
+    fn prop_valuate(_: &Prop, _: &Vars) -> bool {
+        panic!()
+    }
+
+    // AG !(P@crit && Q@crit)
+    let spec = Formula::Not(Box::new(Formula::EU(
+        Box::new(Formula::True),
+        Box::new(Formula::And(
+            Box::new(Formula::At(0, Location::new("crit"))),
+            Box::new(Formula::At(1, Location::new("crit"))),
+        )),
+    )));
+
+    let marks = mark::make_marks(&mut lts, prop_valuate, spec);
+    println!("mutual exclusion holds: {}", marks.holds_at(0));
+    viz::lts("mutex.dot", &lts, marks);
+}
@@ -36,6 +36,7 @@ fn main() {
     let process_p: Process<Vars> = vec![
         ExecUnit {
             src: Location::new("P0"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("x=1"),
                 dst: Location::new("P1"),
@@ -44,10 +45,13 @@ fn main() {
                     vars.x = 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P1"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("y=1"),
                 dst: Location::new("P2"),
@@ -56,10 +60,13 @@ fn main() {
                     vars.y = 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P2"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("z=1"),
                 dst: Location::new("P3"),
@@ -68,10 +75,13 @@ fn main() {
                     vars.z = 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P3"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("y=0"),
                 dst: Location::new("P4"),
@@ -80,10 +90,13 @@ fn main() {
                     vars.y = 0;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P4"),
+            invariant: vec![],
             transs: vec![],
         },
     ];
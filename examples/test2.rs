@@ -30,6 +30,7 @@ fn main() {
     let process_p: Process<Vars> = vec![
         ExecUnit {
             src: Location::new("P0"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("x=1"),
                 dst: Location::new("P1"),
@@ -38,10 +39,13 @@ fn main() {
                     vars.x = 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P1"),
+            invariant: vec![],
             transs: vec![
                 Trans {
                     label: Label::new("x=2"),
@@ -51,6 +55,8 @@ fn main() {
                         vars.x = 2;
                         vars
                     },
+                    clock_guard: vec![],
+                    resets: vec![],
                 },
                 Trans {
                     label: Label::new("x=3"),
@@ -60,6 +66,8 @@ fn main() {
                         vars.x = 3;
                         vars
                     },
+                    clock_guard: vec![],
+                    resets: vec![],
                 },
                 Trans {
                     label: Label::new("x=4"),
@@ -69,11 +77,14 @@ fn main() {
                         vars.x = 4;
                         vars
                     },
+                    clock_guard: vec![],
+                    resets: vec![],
                 },
             ],
         },
         ExecUnit {
             src: Location::new("P2"),
+            invariant: vec![],
             transs: vec![Trans {
                 label: Label::new("x--"),
                 dst: Location::new("P3"),
@@ -82,10 +93,13 @@ fn main() {
                     vars.x -= 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             }],
         },
         ExecUnit {
             src: Location::new("P3"),
+            invariant: vec![],
             transs: vec![],
         },
     ];
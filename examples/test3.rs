@@ -29,6 +29,7 @@ impl fmt::Display for Vars {
 fn main() {
     let process_p: Process<Vars> = vec![ExecUnit {
         src: Location::new("S"),
+        invariant: vec![],
         transs: vec![
             Trans {
                 label: Label::new(""),
@@ -38,6 +39,8 @@ fn main() {
                     vars.x *= 2;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             },
             Trans {
                 label: Label::new(""),
@@ -47,6 +50,8 @@ fn main() {
                     vars.x = vars.x * 2 + 1;
                     vars
                 },
+                clock_guard: vec![],
+                resets: vec![],
             },
         ],
     }];
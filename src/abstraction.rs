@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::{
+    formula::{Formula, Prop},
+    lts::{self, Lts, State, StateId, Trace, STUTTER},
+    mark::{self, Deadlock, Options},
+    process::{Location, Process},
+};
+
+/// What the result on an abstraction tells of the concrete system.
+#[derive(Debug, Clone)]
+pub enum Validation<C> {
+    /// the formula is in ACTL and holds in the abstraction, so it holds concretely.
+    Sound,
+    /// the concrete states of a path following the abstract witness: of the formula if
+    /// it holds, of its negation otherwise. the operands of the witnessed operator are
+    /// propositional, so they hold along the path too, and the result holds concretely.
+    Concrete(Trace, Vec<State<C>>),
+    /// no concrete path follows the abstract witness, which may be an artifact of merging.
+    Spurious(Trace),
+    /// no abstract witness to replay, or one whose operands may not hold on the concrete
+    /// path, such as the `EX p` of `EX EX p`.
+    Inconclusive,
+}
+
+/// The result of checking a formula on an abstraction, in its initial state.
+#[derive(Debug, Clone)]
+pub struct Report<C> {
+    pub holds: bool,
+    pub validation: Validation<C>,
+}
+
+/// Checks `spec` on `Lts::abstract_composition`, with the stuttering semantics of
+/// `Deadlock::Stutter`, and reports how far the result carries over.
+///
+/// a universal ACTL formula holding in the abstraction holds on the concrete system. otherwise
+/// the result rests on an existential witness, of the formula or of its negation, which is
+/// replayed on the concrete states when `mark::witness` provides one.
+pub fn check<C: Clone + Hash + Eq, A: Clone + Hash + Eq>(
+    vars: C,
+    processes: &[Process<C>],
+    abstraction: fn(&C) -> A,
+    prop_valuate: fn(&Prop, &A) -> bool,
+    spec: Formula,
+) -> Result<(Lts<A>, Report<C>), String> {
+    let mut lts = Lts::abstract_composition(vars.clone(), processes, abstraction)?;
+    let options = Options {
+        deadlock: Deadlock::Stutter,
+        ..Default::default()
+    };
+    let marks = mark::make_marks_with(&mut lts, prop_valuate, spec.clone(), &options)?;
+    let holds = marks.holds_at(0);
+    if holds && is_actl(&spec, true) {
+        let report = Report {
+            holds,
+            validation: Validation::Sound,
+        };
+        return Ok((lts, report));
+    }
+    let (witnessed, witness) = if holds {
+        let witness = mark::witness(&lts, &marks, 0);
+        (spec, witness)
+    } else {
+        let negation = match spec {
+            Formula::Not(box f) => f,
+            f => Formula::Not(Box::new(f)),
+        };
+        let marks = mark::make_marks_with(&mut lts, prop_valuate, negation.clone(), &options)?;
+        (negation, mark::witness(&lts, &marks, 0))
+    };
+    let validation = match witness {
+        Some(trace) => match replay(vars, processes, abstraction, &lts, &trace) {
+            Some(states) if has_propositional_operands(&witnessed) => {
+                Validation::Concrete(trace, states)
+            }
+            Some(_) => Validation::Inconclusive,
+            None => Validation::Spurious(trace),
+        },
+        None => Validation::Inconclusive,
+    };
+    Ok((lts, Report { holds, validation }))
+}
+
+/// whether the formula is in ACTL, under an even number of negations if `positive`:
+/// existential operators only occur negated. the atoms which the merging of states does not
+/// preserve, `Enabled` and the absence of `Deadlock`, are excluded too.
+pub fn is_actl(f: &Formula, positive: bool) -> bool {
+    use Formula::*;
+    match f {
+        True | False | Prop(_) | At(_, _) => true,
+        Deadlock => positive,
+        Enabled(_) => !positive,
+        Not(f) => is_actl(f, !positive),
+        And(f, g) | Or(f, g) => is_actl(f, positive) && is_actl(g, positive),
+        Impl(f, g) => is_actl(f, !positive) && is_actl(g, positive),
+        EX(f) | EG(f) | Diamond(_, f) | EGa(_, f) | EGb(_, f) => !positive && is_actl(f, positive),
+        EU(f, g) | EUa(f, _, _, g) | EUb(_, f, g) => {
+            !positive && is_actl(f, positive) && is_actl(g, positive)
+        }
+        AUb(_, f, g) => positive && is_actl(f, positive) && is_actl(g, positive),
+        _ => false,
+    }
+}
+
+/// whether the operands of the outermost operator are propositional over the atoms which
+/// the merging of states preserves, so that they hold on the concrete states exactly when
+/// they hold on the abstract ones.
+fn has_propositional_operands(f: &Formula) -> bool {
+    use Formula::*;
+    fn is_propositional(f: &Formula) -> bool {
+        match f {
+            True | False | Prop(_) | At(_, _) => true,
+            Not(f) => is_propositional(f),
+            And(f, g) | Or(f, g) | Impl(f, g) => is_propositional(f) && is_propositional(g),
+            _ => false,
+        }
+    }
+    match f {
+        EX(f) | EGb(_, f) => is_propositional(f),
+        EU(f, g) | EUb(_, f, g) => is_propositional(f) && is_propositional(g),
+        _ => false,
+    }
+}
+
+/// The concrete states of a path following the trace of the abstraction, searched breadth
+/// first from the initial state among the concrete states each abstract one merges.
+pub fn replay<C: Clone + Hash + Eq, A: Eq>(
+    vars: C,
+    processes: &[Process<C>],
+    abstraction: fn(&C) -> A,
+    lts: &Lts<A>,
+    trace: &Trace,
+) -> Option<Vec<State<C>>> {
+    let merged = |state: &State<C>, state_id: StateId| {
+        let target = &lts.0[state_id].state;
+        state.locations == target.locations && abstraction(&state.vars) == target.vars
+    };
+    let s0 = State {
+        locations: processes
+            .iter()
+            .map(|process| process.first().map(|exec| exec.src.clone()))
+            .collect::<Option<Vec<Location>>>()?,
+        vars,
+        zone: None,
+    };
+    if !merged(&s0, trace.start) {
+        return None;
+    }
+    // the states of each layer, with the index of their parent in the previous one
+    let mut layers: Vec<Vec<(State<C>, usize)>> = vec![vec![(s0, 0)]];
+    for (label, next_id) in trace.steps.iter() {
+        let mut layer = vec![];
+        let mut seen = HashSet::new();
+        for (parent, (state, _)) in layers.last().unwrap().iter().enumerate() {
+            let nexts = lts::successors(processes, state);
+            let mut candidates: Vec<State<C>> = vec![];
+            if label.0 == STUTTER && nexts.is_empty() {
+                candidates.push(state.clone());
+            }
+            for (l, _, next) in nexts {
+                if &l == label {
+                    candidates.push(next);
+                }
+            }
+            for next in candidates {
+                if merged(&next, *next_id) && seen.insert(next.clone()) {
+                    layer.push((next, parent));
+                }
+            }
+        }
+        if layer.is_empty() {
+            return None;
+        }
+        layers.push(layer);
+    }
+    let mut states = vec![];
+    let mut index = 0;
+    for layer in layers.iter().rev() {
+        let (state, parent) = &layer[index];
+        states.push(state.clone());
+        index = *parent;
+    }
+    states.reverse();
+    Some(states)
+}
+
+use std::fmt;
+impl<C> fmt::Display for Report<C> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let result = if self.holds { "holds" } else { "fails" };
+        match self.validation {
+            Validation::Sound => write!(fmt, "holds, soundly for ACTL"),
+            Validation::Concrete(ref trace, _) => {
+                write!(fmt, "{}, validated on concrete states by {}", result, trace)
+            }
+            Validation::Spurious(ref trace) => {
+                write!(
+                    fmt,
+                    "{} in the abstraction only, spurious witness {}",
+                    result, trace
+                )
+            }
+            Validation::Inconclusive => write!(fmt, "{} in the abstraction only", result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{ExecUnit, Label, Trans};
+
+    /// `x` goes 0, 1, 3, then stays at 2.
+    fn process() -> Process<i32> {
+        let step = |label: &str, guard: fn(&i32) -> bool, action: fn(i32) -> i32| Trans {
+            label: Label::new(label),
+            dst: Location::new("L"),
+            guard,
+            action,
+            clock_guard: vec![],
+            resets: vec![],
+        };
+        vec![ExecUnit {
+            src: Location::new("L"),
+            invariant: vec![],
+            transs: vec![
+                step("a", |x| *x == 0, |_| 1),
+                step("b", |x| *x == 1, |_| 3),
+                step("c", |x| *x == 3, |_| 2),
+                step("d", |x| *x == 2, |_| 2),
+            ],
+        }]
+    }
+
+    /// whether `p`, true when `x` is 1 or 2, holds.
+    fn p(x: &i32) -> bool {
+        *x == 1 || *x == 2
+    }
+
+    fn check_p(spec: Formula) -> Report<i32> {
+        let (_, report) = check(0, &[process()], p, |_, p| *p, spec).unwrap();
+        report
+    }
+
+    fn ex(f: Formula) -> Formula {
+        Formula::EX(Box::new(f))
+    }
+
+    #[test]
+    fn propositional_witnesses_are_validated() {
+        let prop = || Formula::Prop("p".to_string());
+        let report = check_p(ex(prop()));
+        assert!(report.holds);
+        match report.validation {
+            Validation::Concrete(trace, states) => {
+                assert_eq!(trace.to_string(), "0 -a-> 1");
+                assert_eq!(states[1].vars, 1);
+            }
+            validation => panic!("{:?}", validation),
+        }
+        let ag = Formula::Not(Box::new(Formula::EU(
+            Box::new(Formula::True),
+            Box::new(Formula::Not(Box::new(Formula::At(0, Location::new("L"))))),
+        )));
+        assert!(matches!(check_p(ag).validation, Validation::Sound));
+    }
+
+    #[test]
+    fn nested_witnesses_are_inconclusive() {
+        // `EX EX p` holds in the abstraction, which merges 0 with 3, but not concretely
+        let report = check_p(ex(ex(Formula::Prop("p".to_string()))));
+        assert!(report.holds);
+        assert!(matches!(report.validation, Validation::Inconclusive));
+        assert_eq!(report.to_string(), "holds in the abstraction only");
+    }
+}
@@ -0,0 +1,225 @@
+use std::hash::Hash;
+
+use crate::{
+    formula::{Formula, Prop},
+    lts::{Lts, StateId},
+    mark,
+};
+
+/// indices of the processes playing together.
+pub type Coalition = Vec<usize>;
+
+/// An ATL formula: the strategy modalities `<<A>>` state that the processes of coalition `A`
+/// can make the path formula hold whatever the other processes do.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Atl {
+    True,
+    False,
+    /// a CTL formula, marked by `mark::make_marks_with`.
+    Atom(Formula),
+    Not(Box<Atl>),
+    And(Box<Atl>, Box<Atl>),
+    Or(Box<Atl>, Box<Atl>),
+    Impl(Box<Atl>, Box<Atl>),
+    /// `<<A>> X f`
+    X(Coalition, Box<Atl>),
+    /// `<<A>> F f`
+    F(Coalition, Box<Atl>),
+    /// `<<A>> G f`
+    G(Coalition, Box<Atl>),
+    /// `<<A>> f U g`
+    U(Coalition, Box<Atl>, Box<Atl>),
+}
+
+/// Evaluates `spec` on every state of the lts.
+///
+/// each step is a game: the coalition picks one of its enabled transitions, and the
+/// adversary may instead take any transition of the other processes. a terminal state
+/// stutters on itself. the modalities are the fixpoints of the states from which the
+/// coalition can force the next step into a set.
+pub fn check<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    spec: &Atl,
+) -> Result<Vec<bool>, String> {
+    use Atl::*;
+    let binary = |lhs: Vec<bool>, rhs: Vec<bool>, op: fn(bool, bool) -> bool| -> Vec<bool> {
+        lhs.into_iter().zip(rhs).map(|(l, r)| op(l, r)).collect()
+    };
+    let n = lts.0.len();
+    Ok(match spec {
+        True => vec![true; n],
+        False => vec![false; n],
+        Atom(f) => mark::satisfying(lts, prop_valuate, f.clone())?,
+        Not(f) => check(lts, prop_valuate, f)?
+            .into_iter()
+            .map(|b| !b)
+            .collect(),
+        And(f, g) => binary(
+            check(lts, prop_valuate, f)?,
+            check(lts, prop_valuate, g)?,
+            |l, r| l && r,
+        ),
+        Or(f, g) => binary(
+            check(lts, prop_valuate, f)?,
+            check(lts, prop_valuate, g)?,
+            |l, r| l || r,
+        ),
+        Impl(f, g) => binary(
+            check(lts, prop_valuate, f)?,
+            check(lts, prop_valuate, g)?,
+            |l, r| !l || r,
+        ),
+        X(coalition, f) => {
+            validate(lts, coalition)?;
+            let sat = check(lts, prop_valuate, f)?;
+            (0..n)
+                .map(|id| can_force(lts, coalition, id, &sat))
+                .collect()
+        }
+        F(coalition, f) => {
+            let eventually = U(coalition.clone(), Box::new(True), f.clone());
+            check(lts, prop_valuate, &eventually)?
+        }
+        G(coalition, f) => {
+            validate(lts, coalition)?;
+            let sat = check(lts, prop_valuate, f)?;
+            // greatest fixpoint: Z = f and <<A>> X Z
+            let mut z = sat.clone();
+            loop {
+                let next: Vec<bool> = (0..n)
+                    .map(|id| sat[id] && can_force(lts, coalition, id, &z))
+                    .collect();
+                if next == z {
+                    break z;
+                }
+                z = next;
+            }
+        }
+        U(coalition, f, g) => {
+            validate(lts, coalition)?;
+            let sat_f = check(lts, prop_valuate, f)?;
+            let sat_g = check(lts, prop_valuate, g)?;
+            // least fixpoint: Z = g or (f and <<A>> X Z)
+            let mut z = sat_g.clone();
+            loop {
+                let next: Vec<bool> = (0..n)
+                    .map(|id| sat_g[id] || (sat_f[id] && can_force(lts, coalition, id, &z)))
+                    .collect();
+                if next == z {
+                    break z;
+                }
+                z = next;
+            }
+        }
+    })
+}
+
+fn validate<T>(lts: &Lts<T>, coalition: &Coalition) -> Result<(), String> {
+    let processes = lts.0.first().map_or(0, |trans| trans.state.locations.len());
+    match coalition.iter().find(|i| **i >= processes) {
+        Some(i) => Err(format!("process {} out of {} processes", i, processes)),
+        None => Ok(()),
+    }
+}
+
+/// whether the coalition can force the next state from `state_id` into `target`:
+/// every transition of the other processes leads there, and so does one of the coalition's
+/// if it can move.
+fn can_force<T>(lts: &Lts<T>, coalition: &Coalition, state_id: StateId, target: &[bool]) -> bool {
+    let trans = &lts.0[state_id];
+    let mut own_enabled = false;
+    let mut own_wins = false;
+    let mut moves = false;
+    for step in trans.steps.iter() {
+        match step.process {
+            Some(i) if coalition.contains(&i) => {
+                own_enabled = true;
+                own_wins |= target[step.dst];
+            }
+            Some(_) => {
+                moves = true;
+                if !target[step.dst] {
+                    return false;
+                }
+            }
+            // a stutter loop belongs to no process
+            None => {}
+        }
+    }
+    if own_enabled {
+        own_wins
+    } else if moves {
+        true
+    } else {
+        target[state_id]
+    }
+}
+
+use std::fmt;
+fn coalition_fmt(coalition: &Coalition) -> String {
+    let indices: Vec<String> = coalition.iter().map(|i| i.to_string()).collect();
+    format!("<<{}>>", indices.join(","))
+}
+
+impl fmt::Display for Atl {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Atl::*;
+        match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
+            Atom(ref f) => write!(fmt, "{}", f),
+            Not(ref f) => write!(fmt, "(not {})", f),
+            And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
+            Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
+            Impl(ref lhs, ref rhs) => write!(fmt, "(impl {} {})", lhs, rhs),
+            X(ref a, ref f) => write!(fmt, "({} X {})", coalition_fmt(a), f),
+            F(ref a, ref f) => write!(fmt, "({} F {})", coalition_fmt(a), f),
+            G(ref a, ref f) => write!(fmt, "({} G {})", coalition_fmt(a), f),
+            U(ref a, ref f, ref g) => write!(fmt, "({} U {} {})", coalition_fmt(a), f, g),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::tests::lts, process::Location};
+
+    /// at 0 process 0 can go to 1, at `B`, and process 1 can block it by moving to 2, from
+    /// which process 0 retries.
+    fn game() -> Lts<i32> {
+        let mut lts = lts(vec![
+            vec![("go", 1, Some(0)), ("block", 2, Some(1))],
+            vec![],
+            vec![("retry", 0, Some(0))],
+        ]);
+        for trans in lts.0.iter_mut() {
+            trans.state.locations.push(Location::new("C"));
+        }
+        lts
+    }
+
+    #[test]
+    fn coalitions_force_outcomes() {
+        let at_b = || Box::new(Atl::Atom(Formula::At(0, Location::new("B"))));
+        let check = |spec: Atl| check(&mut game(), |_, _| false, &spec);
+        assert_eq!(check(Atl::X(vec![0], at_b())), Ok(vec![false, true, false]));
+        assert_eq!(
+            check(Atl::X(vec![0, 1], at_b())),
+            Ok(vec![true, true, false])
+        );
+        assert_eq!(check(Atl::F(vec![0], at_b())), Ok(vec![false, true, false]));
+        assert_eq!(
+            check(Atl::F(vec![0, 1], at_b())),
+            Ok(vec![true, true, true])
+        );
+        // process 1 cannot keep process 0 away from `B`
+        let away = Atl::G(vec![1], Box::new(Atl::Not(at_b())));
+        assert_eq!(check(away), Ok(vec![false, false, false]));
+        assert_eq!(
+            check(Atl::X(vec![2], at_b())),
+            Err("process 2 out of 2 processes".to_string())
+        );
+    }
+}
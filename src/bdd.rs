@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+/// A node of a `Bdd`, standing for the boolean function rooted there.
+pub type Node = usize;
+
+/// level of the variables, which are ordered by their index.
+pub type Var = usize;
+
+/// A reduced ordered binary decision diagram package: every node is unique, so two
+/// functions are equal exactly when their nodes are.
+#[derive(Debug)]
+pub struct Bdd {
+    /// the variable and the low and high children of each node.
+    nodes: Vec<(Var, Node, Node)>,
+    unique: HashMap<(Var, Node, Node), Node>,
+    ite_cache: HashMap<(Node, Node, Node), Node>,
+}
+
+impl Default for Bdd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bdd {
+    pub const FALSE: Node = 0;
+    pub const TRUE: Node = 1;
+
+    pub fn new() -> Self {
+        Bdd {
+            // the terminals sit below every variable
+            nodes: vec![(Var::MAX, 0, 0), (Var::MAX, 1, 1)],
+            unique: HashMap::new(),
+            ite_cache: HashMap::new(),
+        }
+    }
+
+    fn var_of(&self, f: Node) -> Var {
+        self.nodes[f].0
+    }
+
+    fn make(&mut self, var: Var, lo: Node, hi: Node) -> Node {
+        if lo == hi {
+            return lo;
+        }
+        if let Some(node) = self.unique.get(&(var, lo, hi)) {
+            return *node;
+        }
+        let node = self.nodes.len();
+        self.nodes.push((var, lo, hi));
+        self.unique.insert((var, lo, hi), node);
+        node
+    }
+
+    /// the cofactors of `f` for `var` false and true.
+    fn cofactors(&self, f: Node, var: Var) -> (Node, Node) {
+        let (v, lo, hi) = self.nodes[f];
+        if v == var {
+            (lo, hi)
+        } else {
+            (f, f)
+        }
+    }
+
+    /// the function true exactly when the variable is.
+    pub fn var(&mut self, var: Var) -> Node {
+        self.make(var, Self::FALSE, Self::TRUE)
+    }
+
+    /// if `f` then `g` else `h`.
+    pub fn ite(&mut self, f: Node, g: Node, h: Node) -> Node {
+        if f == Self::TRUE || g == h {
+            return g;
+        }
+        if f == Self::FALSE {
+            return h;
+        }
+        if g == Self::TRUE && h == Self::FALSE {
+            return f;
+        }
+        if let Some(node) = self.ite_cache.get(&(f, g, h)) {
+            return *node;
+        }
+        let var = self.var_of(f).min(self.var_of(g)).min(self.var_of(h));
+        let (f0, f1) = self.cofactors(f, var);
+        let (g0, g1) = self.cofactors(g, var);
+        let (h0, h1) = self.cofactors(h, var);
+        let lo = self.ite(f0, g0, h0);
+        let hi = self.ite(f1, g1, h1);
+        let node = self.make(var, lo, hi);
+        self.ite_cache.insert((f, g, h), node);
+        node
+    }
+
+    pub fn not(&mut self, f: Node) -> Node {
+        self.ite(f, Self::FALSE, Self::TRUE)
+    }
+
+    pub fn and(&mut self, f: Node, g: Node) -> Node {
+        self.ite(f, g, Self::FALSE)
+    }
+
+    pub fn or(&mut self, f: Node, g: Node) -> Node {
+        self.ite(f, Self::TRUE, g)
+    }
+
+    pub fn xor(&mut self, f: Node, g: Node) -> Node {
+        let not_g = self.not(g);
+        self.ite(f, not_g, g)
+    }
+
+    pub fn iff(&mut self, f: Node, g: Node) -> Node {
+        let not_g = self.not(g);
+        self.ite(f, g, not_g)
+    }
+
+    /// existentially quantifies the variables for which `vars` is true.
+    pub fn exists(&mut self, f: Node, vars: &[bool]) -> Node {
+        self.and_exists(f, Self::TRUE, vars)
+    }
+
+    /// `exists vars. f and g`, without building the conjunction first.
+    pub fn and_exists(&mut self, f: Node, g: Node, vars: &[bool]) -> Node {
+        fn rec(
+            bdd: &mut Bdd,
+            f: Node,
+            g: Node,
+            vars: &[bool],
+            cache: &mut HashMap<(Node, Node), Node>,
+        ) -> Node {
+            if f == Bdd::FALSE || g == Bdd::FALSE {
+                return Bdd::FALSE;
+            }
+            if f == Bdd::TRUE && g == Bdd::TRUE {
+                return Bdd::TRUE;
+            }
+            if let Some(node) = cache.get(&(f, g)) {
+                return *node;
+            }
+            let var = bdd.var_of(f).min(bdd.var_of(g));
+            let (f0, f1) = bdd.cofactors(f, var);
+            let (g0, g1) = bdd.cofactors(g, var);
+            let lo = rec(bdd, f0, g0, vars, cache);
+            let node = if vars.get(var) == Some(&true) {
+                if lo == Bdd::TRUE {
+                    Bdd::TRUE
+                } else {
+                    let hi = rec(bdd, f1, g1, vars, cache);
+                    bdd.or(lo, hi)
+                }
+            } else {
+                let hi = rec(bdd, f1, g1, vars, cache);
+                bdd.make(var, lo, hi)
+            };
+            cache.insert((f, g), node);
+            node
+        }
+        rec(self, f, g, vars, &mut HashMap::new())
+    }
+
+    /// substitutes the variables by the ones they are mapped to.
+    pub fn rename(&mut self, f: Node, map: &HashMap<Var, Var>) -> Node {
+        fn rec(
+            bdd: &mut Bdd,
+            f: Node,
+            map: &HashMap<Var, Var>,
+            cache: &mut HashMap<Node, Node>,
+        ) -> Node {
+            if f == Bdd::FALSE || f == Bdd::TRUE {
+                return f;
+            }
+            if let Some(node) = cache.get(&f) {
+                return *node;
+            }
+            let (var, lo, hi) = bdd.nodes[f];
+            let lo = rec(bdd, lo, map, cache);
+            let hi = rec(bdd, hi, map, cache);
+            let var = bdd.var(*map.get(&var).unwrap_or(&var));
+            let node = bdd.ite(var, hi, lo);
+            cache.insert(f, node);
+            node
+        }
+        rec(self, f, map, &mut HashMap::new())
+    }
+
+    /// the number of assignments to the variables `0..vars` satisfying `f`.
+    pub fn count(&self, f: Node, vars: usize) -> f64 {
+        fn rec(bdd: &Bdd, f: Node, vars: usize, cache: &mut HashMap<Node, f64>) -> f64 {
+            // counts over the variables from the level of `f` on
+            if f == Bdd::FALSE {
+                return 0.0;
+            }
+            if f == Bdd::TRUE {
+                return 1.0;
+            }
+            if let Some(count) = cache.get(&f) {
+                return *count;
+            }
+            let (var, lo, hi) = bdd.nodes[f];
+            let level = |node: Node| bdd.var_of(node).min(vars);
+            let count = rec(bdd, lo, vars, cache) * 2f64.powi((level(lo) - var - 1) as i32)
+                + rec(bdd, hi, vars, cache) * 2f64.powi((level(hi) - var - 1) as i32);
+            cache.insert(f, count);
+            count
+        }
+        let top = self.var_of(f).min(vars);
+        rec(self, f, vars, &mut HashMap::new()) * 2f64.powi(top as i32)
+    }
+
+    /// some assignment satisfying `f`, as the values of the variables it depends on.
+    pub fn pick(&self, f: Node) -> Option<Vec<(Var, bool)>> {
+        if f == Self::FALSE {
+            return None;
+        }
+        let mut assignment = vec![];
+        let mut node = f;
+        while node != Self::TRUE {
+            let (var, lo, hi) = self.nodes[node];
+            if lo != Self::FALSE {
+                assignment.push((var, false));
+                node = lo;
+            } else {
+                assignment.push((var, true));
+                node = hi;
+            }
+        }
+        Some(assignment)
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use crate::{
+    formula::Prop,
+    lts::{Lts, StateId, Step, Trans, TAU},
+};
+
+/// The quotient of an `Lts` by an equivalence: a state per block of equivalent states.
+#[derive(Debug)]
+pub struct Quotient<T> {
+    /// the states are the first state of each block, and the block of the initial state
+    /// is the initial state 0.
+    pub lts: Lts<T>,
+    /// the block of each state of the original lts.
+    pub blocks: Vec<StateId>,
+}
+
+impl<T> Quotient<T> {
+    /// the values of the original states, from those of the blocks: a result of the
+    /// quotient, e.g. where a formula holds, lifted back.
+    pub fn lift<V: Clone>(&self, values: &[V]) -> Vec<V> {
+        self.blocks
+            .iter()
+            .map(|block| values[*block].clone())
+            .collect()
+    }
+}
+
+/// the blocks of the states by the propositions holding there, and by the locations of the
+/// processes if `with_locations`.
+fn by_props<T>(
+    lts: &Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    props: &[Prop],
+    with_locations: bool,
+) -> Vec<usize> {
+    numbered(lts.0.iter().map(|trans| {
+        let values = props
+            .iter()
+            .map(|p| prop_valuate(p, &trans.state.vars))
+            .collect::<Vec<bool>>();
+        (with_locations.then_some(&trans.state.locations), values)
+    }))
+}
+
+/// The quotient of the lts by strong bisimulation: two states are equivalent if they agree
+/// on the locations and the propositions, and each transition of one is matched by a
+/// transition with the same label of the other into an equivalent state, stuttering loops
+/// only matching stuttering loops.
+///
+/// the partition by the locations and the propositions is refined with the algorithm of
+/// Paige and Tarjan, in `O(m log n)` for `n` states and `m` transitions. CTL formulas whose
+/// atoms are `At`, `Enabled`, `Deadlock` or among the propositions hold in a state exactly
+/// when they hold in its block.
+pub fn strong<T: Clone>(
+    lts: &Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    props: &[Prop],
+) -> Quotient<T> {
+    let blocks = paige_tarjan(lts, &by_props(lts, prop_valuate, props, true));
+    quotient(lts, blocks, false)
+}
+
+/// The quotient of the lts by branching bisimulation, which abstracts from the `TAU`
+/// transitions that `Lts::hide` introduces: two states are equivalent if they agree on the
+/// propositions and each transition of one is matched by the other after `TAU` steps inside
+/// its block, a `TAU` transition also being matched by staying in the block. the locations
+/// are not compared, since the hidden steps of a process mostly change its location: the
+/// state of a block is that of its first state.
+///
+/// the signature of a state collects the transitions leaving its block, and those reachable
+/// by `TAU` steps inside it; the partition is refined by them until it is stable. CTL formulas
+/// without `EX` whose atoms are among the propositions hold in a state exactly when they hold in
+/// its block, under the divergence-blind reading which ignores `TAU` cycles.
+pub fn branching<T: Clone>(
+    lts: &Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    props: &[Prop],
+) -> Quotient<T> {
+    let blocks = refine(by_props(lts, prop_valuate, props, false), |blocks| {
+        let mut signatures: Vec<BTreeSet<(&str, StateId)>> = lts
+            .0
+            .iter()
+            .enumerate()
+            .map(|(state_id, trans)| {
+                trans
+                    .steps
+                    .iter()
+                    .map(|step| (step.label.0.as_str(), blocks[step.dst]))
+                    .filter(|(label, block)| *label != TAU || *block != blocks[state_id])
+                    .collect()
+            })
+            .collect();
+        // inherit the signatures of the inert successors, up to a fixpoint
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (state_id, trans) in lts.0.iter().enumerate() {
+                for step in trans.steps.iter() {
+                    if step.label.0 != TAU
+                        || blocks[step.dst] != blocks[state_id]
+                        || step.dst == state_id
+                    {
+                        continue;
+                    }
+                    let inherited: Vec<(&str, StateId)> = signatures[step.dst]
+                        .difference(&signatures[state_id])
+                        .copied()
+                        .collect();
+                    if !inherited.is_empty() {
+                        signatures[state_id].extend(inherited);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        signatures
+            .into_iter()
+            .map(|signature| signature.into_iter().collect::<Vec<_>>())
+            .collect()
+    });
+    quotient(lts, blocks, true)
+}
+
+/// numbers the keys by their first occurrence.
+pub(crate) fn numbered<K: Hash + Eq>(keys: impl Iterator<Item = K>) -> Vec<usize> {
+    let mut ids = HashMap::new();
+    keys.map(|key| {
+        let next = ids.len();
+        *ids.entry(key).or_insert(next)
+    })
+    .collect()
+}
+
+/// splits the blocks by the signatures of their states until no block splits.
+///
+/// this is the naive signature refinement, recomputing every signature in each round, in
+/// `O(n m)` for `n` states and `m` transitions: the signatures of `branching` inherit
+/// along the inert `TAU` steps, which the splitters of `paige_tarjan` do not follow.
+pub(crate) fn refine<S: Hash + Eq>(
+    blocks: Vec<usize>,
+    signatures: impl Fn(&[usize]) -> Vec<S>,
+) -> Vec<usize> {
+    let mut blocks = blocks;
+    let mut count = blocks.iter().max().map_or(0, |max| max + 1);
+    loop {
+        let refined = numbered(blocks.iter().copied().zip(signatures(&blocks)));
+        let refined_count = refined.iter().max().map_or(0, |max| max + 1);
+        blocks = refined;
+        if refined_count == count {
+            return blocks;
+        }
+        count = refined_count;
+    }
+}
+
+/// A partition of the states which splits in the time of the states it moves: the states
+/// of a block are a range of `elems`, whose marked states come first.
+struct Partition {
+    elems: Vec<StateId>,
+    pos: Vec<usize>,
+    block_of: Vec<usize>,
+    /// the start, end and end of the marked states of each block.
+    ranges: Vec<(usize, usize, usize)>,
+    touched: Vec<usize>,
+}
+
+impl Partition {
+    fn new(blocks: &[usize]) -> Partition {
+        let count = blocks.iter().max().map_or(0, |max| max + 1);
+        let mut elems: Vec<StateId> = (0..blocks.len()).collect();
+        elems.sort_by_key(|state_id| blocks[*state_id]);
+        let mut pos = vec![0; blocks.len()];
+        let mut ranges = vec![(0, 0, 0); count];
+        for (i, state_id) in elems.iter().enumerate() {
+            pos[*state_id] = i;
+            let range = &mut ranges[blocks[*state_id]];
+            if range.1 == 0 {
+                *range = (i, i, i);
+            }
+            range.1 = i + 1;
+        }
+        Partition {
+            elems,
+            pos,
+            block_of: blocks.to_vec(),
+            ranges,
+            touched: vec![],
+        }
+    }
+
+    fn mark(&mut self, state_id: StateId) {
+        let block = self.block_of[state_id];
+        let (_, _, marked) = self.ranges[block];
+        let i = self.pos[state_id];
+        if i < marked {
+            return;
+        }
+        if marked == self.ranges[block].0 {
+            self.touched.push(block);
+        }
+        let other = self.elems[marked];
+        self.elems.swap(i, marked);
+        self.pos[other] = i;
+        self.pos[state_id] = marked;
+        self.ranges[block].2 += 1;
+    }
+
+    /// splits the marked states off their blocks, returning the pairs of the block and the
+    /// new block of its marked states.
+    fn split(&mut self) -> Vec<(usize, usize)> {
+        let mut splits = vec![];
+        for block in std::mem::take(&mut self.touched) {
+            let (start, end, marked) = self.ranges[block];
+            if marked == end {
+                self.ranges[block].2 = start;
+                continue;
+            }
+            let new = self.ranges.len();
+            self.ranges.push((start, marked, start));
+            self.ranges[block] = (marked, end, marked);
+            for state_id in self.elems[start..marked].iter() {
+                self.block_of[*state_id] = new;
+            }
+            splits.push((block, new));
+        }
+        splits
+    }
+
+    fn size(&self, block: usize) -> usize {
+        let (start, end, _) = self.ranges[block];
+        end - start
+    }
+}
+
+/// the coarsest partition refining the blocks where the states of a block have transitions
+/// with the same labels into the same blocks, stuttering loops being told apart.
+///
+/// the blocks are kept stable with respect to the coarser compound blocks, unions of blocks.
+/// a compound block of several blocks is split by its smaller half `B` of one block: a
+/// block splits by whether its states have a transition with a label into `B`, and by
+/// whether all of their transitions with that label into the compound block go into `B`,
+/// which the number of those transitions of each state tells in the time of `B`.
+fn paige_tarjan<T>(lts: &Lts<T>, blocks: &[usize]) -> Vec<usize> {
+    let actions = numbered(lts.0.iter().flat_map(|trans| {
+        trans
+            .steps
+            .iter()
+            .map(|step| (step.process.is_none(), &step.label))
+    }));
+    // the transitions as (src, action, dst), with the transitions into each state
+    let mut transs = vec![];
+    for (state_id, trans) in lts.0.iter().enumerate() {
+        for step in trans.steps.iter() {
+            transs.push((state_id, actions[transs.len()], step.dst));
+        }
+    }
+    let mut into = vec![vec![]; lts.0.len()];
+    for (i, (_, _, dst)) in transs.iter().enumerate() {
+        into[*dst].push(i);
+    }
+    // the number of transitions of a state with an action into a compound block, each
+    // transition knowing its own
+    let mut counts = vec![];
+    let mut count_of = vec![0; transs.len()];
+    let mut records = HashMap::new();
+    for (i, (src, action, _)) in transs.iter().enumerate() {
+        let record = *records.entry((*src, *action)).or_insert_with(|| {
+            counts.push(0);
+            counts.len() - 1
+        });
+        counts[record] += 1;
+        count_of[i] = record;
+    }
+
+    let mut partition = Partition::new(blocks);
+    // stable with respect to the compound block of all the states
+    let mut by_action: HashMap<usize, Vec<StateId>> = HashMap::new();
+    for (src, action, _) in transs.iter() {
+        by_action.entry(*action).or_default().push(*src);
+    }
+    for srcs in by_action.values() {
+        srcs.iter().for_each(|src| partition.mark(*src));
+        partition.split();
+    }
+    let mut compound_of = vec![0; partition.ranges.len()];
+    let mut compounds = vec![(0..partition.ranges.len()).collect::<Vec<usize>>()];
+    let mut pending = if compounds[0].len() > 1 {
+        vec![0]
+    } else {
+        vec![]
+    };
+
+    while let Some(compound) = pending.pop() {
+        let (first, second) = (compounds[compound][0], compounds[compound][1]);
+        let splitter = if partition.size(first) <= partition.size(second) {
+            first
+        } else {
+            second
+        };
+        compounds[compound].retain(|block| *block != splitter);
+        if compounds[compound].len() > 1 {
+            pending.push(compound);
+        }
+        compound_of[splitter] = compounds.len();
+        compounds.push(vec![splitter]);
+
+        let (start, end, _) = partition.ranges[splitter];
+        let mut by_action: HashMap<usize, Vec<usize>> = HashMap::new();
+        for state_id in partition.elems[start..end].iter() {
+            for i in into[*state_id].iter() {
+                by_action.entry(transs[*i].1).or_default().push(*i);
+            }
+        }
+        for incoming in by_action.values() {
+            // the number of transitions of each source into the splitter
+            let mut into_splitter: HashMap<StateId, usize> = HashMap::new();
+            for i in incoming.iter() {
+                *into_splitter.entry(transs[*i].0).or_insert(0) += 1;
+            }
+            // split by having a transition into the splitter, then by having all of those
+            // into the compound block there
+            for only_splitter in [false, true] {
+                for i in incoming.iter() {
+                    let src = transs[*i].0;
+                    if !only_splitter || into_splitter[&src] == counts[count_of[*i]] {
+                        partition.mark(src);
+                    }
+                }
+                for (block, new) in partition.split() {
+                    let compound = compound_of[block];
+                    compound_of.push(compound);
+                    compounds[compound].push(new);
+                    if compounds[compound].len() == 2 {
+                        pending.push(compound);
+                    }
+                }
+            }
+            let mut records = HashMap::new();
+            for i in incoming.iter() {
+                let src = transs[*i].0;
+                counts[count_of[*i]] -= 1;
+                count_of[*i] = *records.entry(src).or_insert_with(|| {
+                    counts.push(into_splitter[&src]);
+                    counts.len() - 1
+                });
+            }
+        }
+    }
+    numbered(partition.block_of.into_iter())
+}
+
+/// the lts of the blocks, with the transitions of all their states, but for the `TAU`
+/// transitions inside a block if `inert` ones are dropped.
+pub(crate) fn quotient<T: Clone>(
+    lts: &Lts<T>,
+    blocks: Vec<usize>,
+    drop_inert: bool,
+) -> Quotient<T> {
+    let count = blocks.iter().max().map_or(0, |max| max + 1);
+    let mut transs: Vec<Option<Trans<T>>> = (0..count).map(|_| None).collect();
+    for (state_id, trans) in lts.0.iter().enumerate() {
+        let block = blocks[state_id];
+        let quotient = transs[block].get_or_insert_with(|| Trans {
+            state: trans.state.clone(),
+            steps: vec![],
+        });
+        for step in trans.steps.iter() {
+            let dst = blocks[step.dst];
+            let inert = step.label.0 == TAU && dst == block;
+            let known = quotient.steps.iter().any(|s| {
+                s.label == step.label
+                    && s.dst == dst
+                    && s.process.is_none() == step.process.is_none()
+            });
+            if (!drop_inert || !inert) && !known {
+                quotient.steps.push(Step {
+                    dst,
+                    ..step.clone()
+                });
+            }
+        }
+    }
+    Quotient {
+        lts: Lts(transs.into_iter().map(Option::unwrap).collect()),
+        blocks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lts::tests::lts;
+
+    fn no_props(_: &Prop, _: &i32) -> bool {
+        false
+    }
+
+    #[test]
+    fn strong_merges_bisimilar_states() {
+        let lts = lts(vec![
+            vec![("a", 1, Some(0)), ("a", 3, Some(0))],
+            vec![("b", 2, Some(0))],
+            vec![],
+            vec![("b", 4, Some(0))],
+            vec![],
+        ]);
+        let quotient = strong(&lts, no_props, &[]);
+        assert_eq!(quotient.blocks, vec![0, 1, 2, 1, 2]);
+        assert_eq!(quotient.lts.0[0].steps.len(), 1);
+        assert_eq!(
+            quotient.lift(&["s", "t", "u"]),
+            vec!["s", "t", "u", "t", "u"]
+        );
+    }
+
+    #[test]
+    fn strong_keeps_locations_apart() {
+        // the terminal states 1 and 2 only differ by the location of process 0
+        let lts = lts(vec![
+            vec![("a", 1, Some(0)), ("a", 2, Some(0))],
+            vec![],
+            vec![],
+        ]);
+        assert_eq!(strong(&lts, no_props, &[]).blocks, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn paige_tarjan_agrees_with_signature_refinement() {
+        let mut seed = 7u64;
+        let mut next = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for _ in 0..20 {
+            let mut steps = vec![];
+            for _ in 0..40 {
+                let mut state_steps = vec![];
+                for _ in 0..next(3) {
+                    state_steps.push((["a", "b"][next(2)], next(40), Some(0)));
+                }
+                steps.push(state_steps);
+            }
+            let mut lts = lts(steps);
+            lts.add_stutter_loops();
+            let initial = by_props(&lts, no_props, &[], true);
+            let signatures = refine(initial.clone(), |blocks| {
+                lts.0
+                    .iter()
+                    .map(|trans| {
+                        let signature: BTreeSet<(bool, &str, StateId)> = trans
+                            .steps
+                            .iter()
+                            .map(|s| (s.process.is_none(), s.label.0.as_str(), blocks[s.dst]))
+                            .collect();
+                        signature
+                    })
+                    .collect()
+            });
+            assert_eq!(paige_tarjan(&lts, &initial), signatures);
+        }
+    }
+
+    #[test]
+    fn branching_merges_across_inert_tau() {
+        let lts = lts(vec![
+            vec![(TAU, 2, Some(0))],
+            vec![],
+            vec![("a", 1, Some(0))],
+        ]);
+        let quotient = branching(&lts, no_props, &[]);
+        assert_eq!(quotient.blocks, vec![0, 1, 0]);
+        // the inert step is dropped
+        let labels: Vec<&str> = quotient.lts.0[0]
+            .steps
+            .iter()
+            .map(|s| s.label.0.as_str())
+            .collect();
+        assert_eq!(labels, vec!["a"]);
+    }
+
+    #[test]
+    fn branching_keeps_a_tau_losing_a_choice() {
+        // the `b` of state 0 is lost by its `TAU` step
+        let lts = lts(vec![
+            vec![(TAU, 2, Some(0)), ("b", 1, Some(0))],
+            vec![],
+            vec![("a", 1, Some(0))],
+        ]);
+        let quotient = branching(&lts, no_props, &[]);
+        assert_eq!(quotient.blocks, vec![0, 1, 2]);
+        assert_eq!(quotient.lts.0[0].steps.len(), 2);
+    }
+
+    #[test]
+    fn hidden_protocol_steps_collapse() {
+        use crate::process::{ExecUnit, Label, Location, Trans};
+        // `A -prepare-> B -commit-> C -out-> A`, where only `out` is observable
+        let exec = |src: &str, label: &str, dst: &str| ExecUnit {
+            src: Location::new(src),
+            invariant: vec![],
+            transs: vec![Trans {
+                label: Label::new(label),
+                dst: Location::new(dst),
+                guard: |_| true,
+                action: |x| x,
+                clock_guard: vec![],
+                resets: vec![],
+            }],
+        };
+        let process = vec![
+            exec("A", "prepare", "B"),
+            exec("B", "commit", "C"),
+            exec("C", "out", "A"),
+        ];
+        let mut lts = Lts::concurrent_composition(0, vec![process]).unwrap();
+        assert_eq!(strong(&lts, no_props, &[]).lts.0.len(), 3);
+        lts.hide(&[Label::new("prepare"), Label::new("commit")]);
+        let quotient = branching(&lts, no_props, &[]);
+        assert_eq!(quotient.blocks, vec![0, 0, 0]);
+        let steps: Vec<(&str, StateId)> = quotient.lts.0[0]
+            .steps
+            .iter()
+            .map(|s| (s.label.0.as_str(), s.dst))
+            .collect();
+        assert_eq!(steps, vec![("out", 0)]);
+    }
+}
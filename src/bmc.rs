@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+
+use crate::{
+    formula::{Formula, Prop},
+    lts::{State, Trace},
+    process::{Label, Location},
+    sat::{Lit, Solver},
+    symbolic::{bits_for, blast, equal, truth, Expr, Logic, Model},
+};
+
+/// The Tseitin encoding of gates into the clauses of a `Solver`, sharing equal gates.
+pub(crate) struct Cnf {
+    pub(crate) solver: Solver,
+    true_lit: Lit,
+    gates: HashMap<(u8, Lit, Lit, Lit), Lit>,
+}
+
+impl Cnf {
+    pub(crate) fn new() -> Self {
+        let mut solver = Solver::new();
+        let true_lit = Lit::new(solver.new_var(), true);
+        solver.add_clause(&[true_lit]);
+        Cnf {
+            solver,
+            true_lit,
+            gates: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn fresh(&mut self) -> Lit {
+        Lit::new(self.solver.new_var(), true)
+    }
+
+    /// the value of the literal in the last satisfying assignment.
+    pub(crate) fn value(&self, lit: Lit) -> bool {
+        self.solver.model_value(lit.var()) == lit.is_positive()
+    }
+
+    /// the gate for `key`, built by adding the clauses for a fresh literal if new.
+    fn gate(
+        &mut self,
+        key: (u8, Lit, Lit, Lit),
+        clauses: fn(Lit, Lit, Lit, Lit) -> Vec<Vec<Lit>>,
+    ) -> Lit {
+        if let Some(lit) = self.gates.get(&key) {
+            return *lit;
+        }
+        let out = self.fresh();
+        for clause in clauses(out, key.1, key.2, key.3) {
+            self.solver.add_clause(&clause);
+        }
+        self.gates.insert(key, out);
+        out
+    }
+}
+
+impl Logic for Cnf {
+    type Bit = Lit;
+    fn constant(&mut self, value: bool) -> Lit {
+        if value {
+            self.true_lit
+        } else {
+            !self.true_lit
+        }
+    }
+    fn not(&mut self, a: Lit) -> Lit {
+        !a
+    }
+    fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let t = self.true_lit;
+        if a == !t || b == !t || a == !b {
+            return !t;
+        }
+        if a == t || a == b {
+            return b;
+        }
+        if b == t {
+            return a;
+        }
+        let (a, b) = (a.min(b), a.max(b));
+        self.gate((0, a, b, t), |c, a, b, _| {
+            vec![vec![!c, a], vec![!c, b], vec![c, !a, !b]]
+        })
+    }
+    fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        let nor = self.and(!a, !b);
+        !nor
+    }
+    fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let t = self.true_lit;
+        if a.var() == t.var() {
+            return if a == t { !b } else { b };
+        }
+        if b.var() == t.var() {
+            return if b == t { !a } else { a };
+        }
+        if a == b {
+            return !t;
+        }
+        if a == !b {
+            return t;
+        }
+        // xor(not a, b) = not xor(a, b)
+        let flip = !a.is_positive() ^ !b.is_positive();
+        let (a, b) = (Lit::new(a.var(), true), Lit::new(b.var(), true));
+        let (a, b) = (a.min(b), a.max(b));
+        let out = self.gate((1, a, b, t), |c, a, b, _| {
+            vec![
+                vec![!c, a, b],
+                vec![!c, !a, !b],
+                vec![c, !a, b],
+                vec![c, a, !b],
+            ]
+        });
+        if flip {
+            !out
+        } else {
+            out
+        }
+    }
+    fn ite(&mut self, c: Lit, a: Lit, b: Lit) -> Lit {
+        let t = self.true_lit;
+        if c == t || a == b {
+            return a;
+        }
+        if c == !t {
+            return b;
+        }
+        self.gate((2, c, a, b), |x, c, a, b| {
+            vec![
+                vec![!c, !a, x],
+                vec![!c, a, !x],
+                vec![c, !b, x],
+                vec![c, b, !x],
+            ]
+        })
+    }
+}
+
+/// The model unrolled into clauses: the bits of a sequence of states, and the transitions
+/// between consecutive ones. a state is encoded as in `symbolic::Symbolic`.
+pub(crate) struct Unrolling<'a> {
+    model: &'a Model,
+    pub(crate) cnf: Cnf,
+    width: usize,
+    locations: Vec<Vec<Location>>,
+    slot_bits: Vec<usize>,
+    /// the bits of each variable then location, in each state.
+    pub(crate) states: Vec<Vec<Vec<Lit>>>,
+    /// the label and the selector of each transition, at each step.
+    steps: Vec<Vec<(Label, Lit)>>,
+}
+
+impl<'a> Unrolling<'a> {
+    pub(crate) fn new(model: &'a Model) -> Result<Self, String> {
+        model.validate()?;
+        let locations = model.locations();
+        let slot_bits = model
+            .vars
+            .iter()
+            .map(|var| var.bits)
+            .chain(locations.iter().map(|locs| bits_for(locs.len())))
+            .collect();
+        Ok(Unrolling {
+            model,
+            cnf: Cnf::new(),
+            width: model.width(),
+            locations,
+            slot_bits,
+            states: vec![],
+            steps: vec![],
+        })
+    }
+
+    /// adds a state, with each process at one of its locations.
+    pub(crate) fn add_state(&mut self) -> usize {
+        let mut state = vec![];
+        for bits in self.slot_bits.clone() {
+            state.push((0..bits).map(|_| self.cnf.fresh()).collect::<Vec<Lit>>());
+        }
+        let n_vars = self.model.vars.len();
+        for (p, locations) in self.locations.iter().enumerate() {
+            let bits = &state[n_vars + p];
+            for code in locations.len()..(1 << bits.len()) {
+                let clause: Vec<Lit> = bits
+                    .iter()
+                    .enumerate()
+                    .map(|(j, bit)| if (code >> j) & 1 == 1 { !*bit } else { *bit })
+                    .collect();
+                self.cnf.solver.add_clause(&clause);
+            }
+        }
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// the literal for the state holding `value` in the slot.
+    fn value(&mut self, step: usize, slot: usize, value: u64) -> Lit {
+        let bits = self.states[step][slot].clone();
+        let constant: Vec<Lit> = (0..bits.len())
+            .map(|j| self.cnf.constant((value >> j) & 1 == 1))
+            .collect();
+        equal(&mut self.cnf, &bits, &constant)
+    }
+
+    fn at(&mut self, step: usize, process: usize, location: &Location) -> Lit {
+        let slot = self.model.vars.len() + process;
+        match self.locations[process].iter().position(|l| l == location) {
+            Some(code) => self.value(step, slot, code as u64),
+            None => self.cnf.constant(false),
+        }
+    }
+
+    /// the literal for the state being initial.
+    pub(crate) fn init(&mut self, step: usize) -> Lit {
+        let mut init = self.cnf.constant(true);
+        for (i, var) in self.model.vars.iter().enumerate() {
+            let value = self.value(step, i, var.init);
+            init = self.cnf.and(init, value);
+        }
+        for p in 0..self.locations.len() {
+            let value = self.value(step, self.model.vars.len() + p, 0);
+            init = self.cnf.and(init, value);
+        }
+        init
+    }
+
+    /// the literal for each transition of the model being enabled in the state.
+    fn enabled(&mut self, step: usize) -> Vec<(Label, Lit)> {
+        let n_vars = self.model.vars.len();
+        let vars = self.states[step][..n_vars].to_vec();
+        let mut enabled = vec![];
+        for (p, process) in self.model.processes.iter().enumerate() {
+            for exec in process.iter() {
+                let at_src = self.at(step, p, &exec.src);
+                for trans in exec.transs.iter() {
+                    let guard = truth(&mut self.cnf, &trans.guard, &vars, self.width);
+                    enabled.push((trans.label.clone(), self.cnf.and(at_src, guard)));
+                }
+            }
+        }
+        enabled
+    }
+
+    /// adds a state and the constraint that one transition leads to it from the last state.
+    pub(crate) fn add_step(&mut self) {
+        let fired = self.add_optional_step();
+        self.cnf.solver.add_clause(&[fired]);
+    }
+
+    /// adds a state, which one transition leads to from the last state if the returned
+    /// literal is true, and is unconstrained otherwise.
+    pub(crate) fn add_optional_step(&mut self) -> Lit {
+        let from = self.states.len() - 1;
+        let to = self.add_state();
+        let n_vars = self.model.vars.len();
+        let vars = self.states[from][..n_vars].to_vec();
+        let mut selectors = vec![];
+        for (p, process) in self.model.processes.iter().enumerate() {
+            for exec in process.iter() {
+                for trans in exec.transs.iter() {
+                    let selector = self.cnf.fresh();
+                    let mut effects = vec![
+                        self.at(from, p, &exec.src),
+                        truth(&mut self.cnf, &trans.guard, &vars, self.width),
+                        self.at(to, p, &trans.dst),
+                    ];
+                    for slot in 0..self.slot_bits.len() {
+                        if slot == n_vars + p {
+                            continue;
+                        }
+                        let next = self.states[to][slot].clone();
+                        let assigned = match trans.updates.iter().find(|(var, _)| *var == slot) {
+                            Some((_, expr)) => blast(&mut self.cnf, expr, &vars, self.width),
+                            None => self.states[from][slot].clone(),
+                        };
+                        effects.push(equal(&mut self.cnf, &next, &assigned[..next.len()]));
+                    }
+                    for effect in effects {
+                        self.cnf.solver.add_clause(&[!selector, effect]);
+                    }
+                    selectors.push((trans.label.clone(), selector));
+                }
+            }
+        }
+        let fired = self.cnf.fresh();
+        let clause: Vec<Lit> = std::iter::once(!fired)
+            .chain(selectors.iter().map(|(_, selector)| *selector))
+            .collect();
+        self.cnf.solver.add_clause(&clause);
+        self.steps.push(selectors);
+        fired
+    }
+
+    /// the literal for the expression being true in the state.
+    pub(crate) fn expr(&mut self, expr: &Expr, step: usize) -> Lit {
+        let vars = self.states[step][..self.model.vars.len()].to_vec();
+        truth(&mut self.cnf, expr, &vars, self.width)
+    }
+
+    /// the label and the selector of each transition of the model, in the order of
+    /// `Model::processes`, at the step from the state.
+    pub(crate) fn selectors(&self, step: usize) -> &[(Label, Lit)] {
+        &self.steps[step]
+    }
+
+    /// the literal for the state formula holding in the state.
+    pub(crate) fn formula(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        f: &Formula,
+        step: usize,
+    ) -> Result<Lit, String> {
+        use Formula::*;
+        Ok(match f {
+            True => self.cnf.constant(true),
+            False => self.cnf.constant(false),
+            Prop(p) => {
+                let expr = prop_expr(p).ok_or_else(|| format!("unknown proposition {}", p))?;
+                self.expr(&expr, step)
+            }
+            At(process, location) => {
+                if *process >= self.locations.len() {
+                    return Err(format!("no process {}", process));
+                }
+                self.at(step, *process, location)
+            }
+            Deadlock => {
+                let mut live = self.cnf.constant(false);
+                for (_, enabled) in self.enabled(step) {
+                    live = self.cnf.or(live, enabled);
+                }
+                !live
+            }
+            Enabled(label) => {
+                let mut live = self.cnf.constant(false);
+                for (l, enabled) in self.enabled(step) {
+                    if &l == label {
+                        live = self.cnf.or(live, enabled);
+                    }
+                }
+                live
+            }
+            Not(f) => !self.formula(prop_expr, f, step)?,
+            And(f, g) => {
+                let (f, g) = (
+                    self.formula(prop_expr, f, step)?,
+                    self.formula(prop_expr, g, step)?,
+                );
+                self.cnf.and(f, g)
+            }
+            Or(f, g) => {
+                let (f, g) = (
+                    self.formula(prop_expr, f, step)?,
+                    self.formula(prop_expr, g, step)?,
+                );
+                self.cnf.or(f, g)
+            }
+            Impl(f, g) => {
+                let (f, g) = (
+                    self.formula(prop_expr, f, step)?,
+                    self.formula(prop_expr, g, step)?,
+                );
+                self.cnf.or(!f, g)
+            }
+            _ => return Err(format!("{} is not a state formula", f)),
+        })
+    }
+
+    /// the state in the last satisfying assignment.
+    pub(crate) fn state(&self, step: usize) -> State<Vec<u64>> {
+        let decode = |bits: &Vec<Lit>| {
+            bits.iter()
+                .enumerate()
+                .map(|(j, bit)| (self.cnf.value(*bit) as u64) << j)
+                .sum::<u64>()
+        };
+        let n_vars = self.model.vars.len();
+        State {
+            vars: self.states[step][..n_vars].iter().map(decode).collect(),
+            locations: self.states[step][n_vars..]
+                .iter()
+                .zip(self.locations.iter())
+                .map(|(bits, locations)| locations[decode(bits) as usize].clone())
+                .collect(),
+            zone: None,
+        }
+    }
+
+    /// the path through the first `len` states in the last satisfying assignment.
+    pub(crate) fn path(&self, len: usize) -> Path {
+        Path {
+            states: (0..len).map(|step| self.state(step)).collect(),
+            trace: Trace {
+                start: 0,
+                steps: (1..len).map(|step| (self.label(step - 1), step)).collect(),
+            },
+        }
+    }
+
+    /// the label of the transition taken from the state in the last satisfying assignment.
+    pub(crate) fn label(&self, step: usize) -> Label {
+        let (label, _) = self.steps[step]
+            .iter()
+            .find(|(_, selector)| self.cnf.value(*selector))
+            .unwrap();
+        label.clone()
+    }
+
+    /// the values of the bits of the initial state.
+    pub(crate) fn init_bits(&self) -> Vec<Vec<bool>> {
+        // the locations beyond the variables start at code 0
+        self.slot_bits
+            .iter()
+            .enumerate()
+            .map(|(slot, bits)| {
+                let value = self.model.vars.get(slot).map_or(0, |var| var.init);
+                (0..*bits).map(|j| (value >> j) & 1 == 1).collect()
+            })
+            .collect()
+    }
+}
+
+/// A path through the states of a `Model`: the state ids of `trace` index `states`,
+/// which hold the values of the variables. `cegar::check_process` gives paths through the
+/// states of processes over `V`.
+#[derive(Debug, Clone)]
+pub struct Path<V = Vec<u64>> {
+    pub states: Vec<State<V>>,
+    pub trace: Trace,
+}
+
+/// A shortest path of at most `bound` steps from the initial state to a state satisfying
+/// the state formula `target`: a witness of `EF target`.
+///
+/// the model is unrolled one step at a time, each depth being a SAT query.
+pub fn find(
+    model: &Model,
+    prop_expr: fn(&Prop) -> Option<Expr>,
+    target: &Formula,
+    bound: usize,
+) -> Result<Option<Path>, String> {
+    let mut unrolling = Unrolling::new(model)?;
+    unrolling.add_state();
+    let init = unrolling.init(0);
+    unrolling.cnf.solver.add_clause(&[init]);
+    for depth in 0..=bound {
+        if depth > 0 {
+            unrolling.add_step();
+        }
+        let goal = unrolling.formula(prop_expr, target, depth)?;
+        if unrolling.cnf.solver.solve_with(&[goal]) {
+            return Ok(Some(unrolling.path(depth + 1)));
+        }
+    }
+    Ok(None)
+}
+
+/// A shortest path of at most `bound` steps to a state violating the state formula
+/// `invariant`: a counterexample to `AG invariant`. `None` only tells that the invariant
+/// holds for the first `bound` steps.
+pub fn check_invariant(
+    model: &Model,
+    prop_expr: fn(&Prop) -> Option<Expr>,
+    invariant: &Formula,
+    bound: usize,
+) -> Result<Option<Path>, String> {
+    find(
+        model,
+        prop_expr,
+        &Formula::Not(Box::new(invariant.clone())),
+        bound,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::{
+        tests::{below, counter, prop_expr},
+        ExecUnit, Trans,
+    };
+
+    /// the counter of 3 bits up to 5, with process 1 able to jump it once from 0 to 4.
+    fn model() -> Model {
+        let mut model = counter(3, 5);
+        model.processes.push(vec![ExecUnit {
+            src: Location::new("J"),
+            transs: vec![Trans {
+                label: Label::new("jump"),
+                dst: Location::new("K"),
+                guard: Expr::Eq(Box::new(Expr::Var(0)), Box::new(Expr::Const(0))),
+                updates: vec![(0, Expr::Const(4))],
+            }],
+        }]);
+        model
+    }
+
+    fn labels(path: &Path) -> Vec<&str> {
+        path.trace
+            .steps
+            .iter()
+            .map(|(label, _)| label.0.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn paths_are_shortest() {
+        let model = model();
+        let at_least_4 = Formula::Not(Box::new(below(4)));
+        assert!(find(&model, prop_expr, &at_least_4, 0).unwrap().is_none());
+        let path = find(&model, prop_expr, &at_least_4, 5).unwrap().unwrap();
+        assert_eq!(labels(&path), vec!["jump"]);
+        assert_eq!(path.states[path.trace.last()].vars, vec![4]);
+        assert_eq!(
+            path.states[path.trace.last()].locations,
+            vec![Location::new("L"), Location::new("K")]
+        );
+        let path = check_invariant(&model, prop_expr, &below(5), 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(labels(&path), vec!["jump", "inc"]);
+        assert!(check_invariant(&model, prop_expr, &below(6), 8)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,641 @@
+//! Predicate abstraction with counterexample-guided refinement.
+//!
+//! `check` abstracts a `symbolic::Model` by `symbolic::Expr`s over its variables: the
+//! abstract transitions are enumerated with a SAT solver, and the weakest preconditions
+//! substitute the updates into the expressions. `check_process` abstracts processes by
+//! `Predicate`s over their variables `T`: the abstract states merge the reachable concrete
+//! ones, as in `Lts::abstract_composition`, and the weakest precondition of a predicate is
+//! its composition with the action of a transition.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::{
+    bmc::{Path, Unrolling},
+    formula::{Formula, Prop},
+    lts::{self, Lts, State, Trace},
+    process::{Label, Process},
+    sat::Lit,
+    symbolic::{Expr, Logic, Model},
+};
+
+/// A named predicate over the variables `T` of processes.
+#[derive(Clone)]
+pub struct Predicate<T> {
+    pub name: String,
+    pub holds: Rc<dyn Fn(&T) -> bool>,
+}
+
+impl<T> Predicate<T> {
+    pub fn new(name: &str, holds: impl Fn(&T) -> bool + 'static) -> Self {
+        Predicate {
+            name: name.to_string(),
+            holds: Rc::new(holds),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Predicate<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.name)
+    }
+}
+
+/// The abstraction of a `Model` or of processes by predicates over their variables: an
+/// abstract state keeps the locations and whether each predicate holds.
+#[derive(Debug)]
+pub struct Abstraction<P = Expr> {
+    pub predicates: Vec<P>,
+    pub lts: Lts<Vec<bool>>,
+}
+
+#[derive(Debug)]
+pub enum Outcome<P = Expr, V = Vec<u64>> {
+    /// the invariant holds in every abstract state, hence in every concrete one.
+    Holds(Abstraction<P>),
+    /// a concrete path to a state violating the invariant.
+    Violated(Path<V>),
+    /// the refinements ran out before the abstraction was precise enough.
+    Unknown(Abstraction<P>),
+}
+
+/// Checks `AG invariant` for a state formula `invariant` by counterexample-guided
+/// abstraction refinement, starting from the predicates and those of the invariant.
+///
+/// a shortest abstract path to a violation is replayed on the concrete model through the
+/// guards and the updates of the transitions. if it is spurious, the predicates are
+/// refined with the weakest preconditions of the predicates and the guard of the step
+/// where the replay fails.
+pub fn check(
+    model: &Model,
+    prop_expr: fn(&Prop) -> Option<Expr>,
+    invariant: &Formula,
+    predicates: Vec<Expr>,
+    max_refinements: usize,
+) -> Result<Outcome, String> {
+    let mut predicates = predicates;
+    let mut props = vec![];
+    collect_props(invariant, &mut props);
+    for p in props {
+        let expr = prop_expr(p).ok_or_else(|| format!("unknown proposition {}", p))?;
+        if !predicates.contains(&expr) {
+            predicates.push(expr);
+        }
+    }
+    for round in 0..=max_refinements {
+        let abstraction = Abstraction {
+            lts: abstract_lts(model, &predicates)?,
+            predicates: predicates.clone(),
+        };
+        let prop_index = |p: &Prop| {
+            let expr = prop_expr(p).ok_or_else(|| format!("unknown proposition {}", p))?;
+            Ok(predicates.iter().position(|q| q == &expr).unwrap())
+        };
+        let target = match violation(&abstraction.lts, &prop_index, invariant)? {
+            Some(target) => target,
+            None => return Ok(Outcome::Holds(abstraction)),
+        };
+        let trace = lts::shortest_trace(&abstraction.lts, target);
+        let failing = match replay(model, &abstraction, &trace)? {
+            Ok(path) => return Ok(Outcome::Violated(path)),
+            Err(failing) => failing,
+        };
+        // an initial abstract state without a concrete one leaves no step to refine
+        if failing == 0 {
+            return Ok(Outcome::Unknown(abstraction));
+        }
+        let refined = refine(model, &abstraction, &trace, failing);
+        if refined.is_empty() || round == max_refinements {
+            return Ok(Outcome::Unknown(abstraction));
+        }
+        predicates.extend(refined);
+    }
+    unreachable!()
+}
+
+/// `check` for processes over the variables `T`, whose propositions `prop_valuate` reads.
+/// each proposition of the invariant is a predicate named after it.
+///
+/// the abstract path is replayed through the guards and the actions of the transitions,
+/// and a spurious one refines the predicates with the guards of the failing step and the
+/// predicates after its actions. since predicates cannot be compared, a refinement is
+/// only kept if it splits an abstract state.
+pub fn check_process<T: Clone + Hash + Eq + 'static>(
+    vars: T,
+    processes: &[Process<T>],
+    prop_valuate: fn(&Prop, &T) -> bool,
+    invariant: &Formula,
+    predicates: Vec<Predicate<T>>,
+    max_refinements: usize,
+) -> Result<Outcome<Predicate<T>, T>, String> {
+    let mut predicates = predicates;
+    let mut props = vec![];
+    collect_props(invariant, &mut props);
+    for p in props {
+        if predicates.iter().all(|predicate| &predicate.name != p) {
+            let prop = p.clone();
+            predicates.push(Predicate::new(p, move |vars| prop_valuate(&prop, vars)));
+        }
+    }
+    let s0 = lts::init_state(vars, processes)?;
+    let reachable: Vec<State<T>> = lts::bfs(s0.clone(), |state| {
+        lts::successors(processes, state)
+            .into_iter()
+            .map(|(label, process_id, next)| (label, process_id, 1.0, next))
+            .collect()
+    })
+    .0
+    .into_iter()
+    .map(|trans| trans.state)
+    .collect();
+    for round in 0..=max_refinements {
+        let values = |vars: &T| -> Vec<bool> {
+            predicates
+                .iter()
+                .map(|predicate| (predicate.holds)(vars))
+                .collect()
+        };
+        let abstraction = Abstraction {
+            lts: Lts::abstract_composition(s0.vars.clone(), processes, values)?,
+            predicates: predicates.clone(),
+        };
+        let prop_index = |p: &Prop| {
+            Ok(predicates
+                .iter()
+                .position(|predicate| &predicate.name == p)
+                .unwrap())
+        };
+        let target = match violation(&abstraction.lts, &prop_index, invariant)? {
+            Some(target) => target,
+            None => return Ok(Outcome::Holds(abstraction)),
+        };
+        let trace = lts::shortest_trace(&abstraction.lts, target);
+        // the initial abstract state is that of the initial concrete one
+        let failing = match replay_process(&s0, processes, &abstraction, &trace) {
+            Ok(path) => return Ok(Outcome::Violated(path)),
+            Err(failing) => failing,
+        };
+        let refined = refine_process(processes, &reachable, &abstraction, &trace, failing);
+        if refined.is_empty() || round == max_refinements {
+            return Ok(Outcome::Unknown(abstraction));
+        }
+        predicates.extend(refined);
+    }
+    unreachable!()
+}
+
+fn collect_props<'f>(f: &'f Formula, acc: &mut Vec<&'f Prop>) {
+    use Formula::*;
+    match f {
+        Prop(p) => acc.push(p),
+        Not(f) => collect_props(f, acc),
+        And(f, g) | Or(f, g) | Impl(f, g) => {
+            collect_props(f, acc);
+            collect_props(g, acc);
+        }
+        _ => {}
+    }
+}
+
+/// the first abstract state violating the invariant.
+fn violation(
+    lts: &Lts<Vec<bool>>,
+    prop_index: &dyn Fn(&Prop) -> Result<usize, String>,
+    invariant: &Formula,
+) -> Result<Option<usize>, String> {
+    for (state_id, trans) in lts.0.iter().enumerate() {
+        if !holds(prop_index, invariant, &trans.state)? {
+            return Ok(Some(state_id));
+        }
+    }
+    Ok(None)
+}
+
+/// whether the state formula holds in the abstract state, where the value of each
+/// proposition is that of the predicate at its index.
+fn holds(
+    prop_index: &dyn Fn(&Prop) -> Result<usize, String>,
+    f: &Formula,
+    state: &State<Vec<bool>>,
+) -> Result<bool, String> {
+    use Formula::*;
+    let holds = |f: &Formula| holds(prop_index, f, state);
+    Ok(match f {
+        True => true,
+        False => false,
+        Prop(p) => state.vars[prop_index(p)?],
+        At(process, location) => match state.locations.get(*process) {
+            Some(l) => l == location,
+            None => return Err(format!("no process {}", process)),
+        },
+        Not(f) => !holds(f)?,
+        And(f, g) => holds(f)? && holds(g)?,
+        Or(f, g) => holds(f)? || holds(g)?,
+        Impl(f, g) => !holds(f)? || holds(g)?,
+        _ => return Err(format!("{} is not a state formula over the predicates", f)),
+    })
+}
+
+/// the existential abstraction of the model: an abstract transition for each concrete one
+/// between states with these predicate values, enumerated with a SAT solver.
+fn abstract_lts(model: &Model, predicates: &[Expr]) -> Result<Lts<Vec<bool>>, String> {
+    let mut unrolling = Unrolling::new(model)?;
+    unrolling.add_state();
+    unrolling.add_step();
+    let now: Vec<Lit> = predicates.iter().map(|p| unrolling.expr(p, 0)).collect();
+    let next: Vec<Lit> = predicates.iter().map(|p| unrolling.expr(p, 1)).collect();
+    let mut transs = vec![];
+    for (p, process) in model.processes.iter().enumerate() {
+        for exec in process.iter() {
+            for trans in exec.transs.iter() {
+                transs.push((p, &exec.src, &trans.label, &trans.dst));
+            }
+        }
+    }
+    let selectors: Vec<Lit> = unrolling.selectors(0).iter().map(|(_, s)| *s).collect();
+
+    let values: Vec<u64> = model.vars.iter().map(|var| var.init).collect();
+    let init = State {
+        vars: predicates
+            .iter()
+            .map(|p| p.eval(&values, model.width()) != 0)
+            .collect(),
+        locations: model.locations().iter().map(|l| l[0].clone()).collect(),
+        zone: None,
+    };
+    let unrolling = RefCell::new(unrolling);
+    let next_func = |state: &State<Vec<bool>>| -> Vec<(Label, usize, f64, State<Vec<bool>>)> {
+        let mut unrolling = unrolling.borrow_mut();
+        let mut successors = vec![];
+        for ((p, src, label, dst), selector) in transs.iter().zip(selectors.iter()) {
+            if &state.locations[*p] != *src {
+                continue;
+            }
+            let activation = unrolling.cnf.fresh();
+            let mut assumptions: Vec<Lit> = now
+                .iter()
+                .zip(state.vars.iter())
+                .map(|(lit, value)| if *value { *lit } else { !*lit })
+                .collect();
+            assumptions.push(*selector);
+            assumptions.push(activation);
+            // all the values of the predicates after the transition, each blocked once found
+            while unrolling.cnf.solver.solve_with(&assumptions) {
+                let values: Vec<bool> = next.iter().map(|lit| unrolling.cnf.value(*lit)).collect();
+                let mut locations = state.locations.clone();
+                locations[*p] = (*dst).clone();
+                let clause: Vec<Lit> = std::iter::once(!activation)
+                    .chain(
+                        next.iter()
+                            .zip(values.iter())
+                            .map(|(lit, value)| if *value { !*lit } else { *lit }),
+                    )
+                    .collect();
+                unrolling.cnf.solver.add_clause(&clause);
+                let successor = State {
+                    vars: values,
+                    locations,
+                    zone: None,
+                };
+                successors.push(((*label).clone(), *p, 1.0, successor));
+            }
+            unrolling.cnf.solver.add_clause(&[!activation]);
+        }
+        successors
+    };
+    Ok(lts::bfs(init, next_func))
+}
+
+/// a concrete path following the abstract trace, or the number of steps after which no
+/// concrete path follows it.
+fn replay(
+    model: &Model,
+    abstraction: &Abstraction,
+    trace: &Trace,
+) -> Result<Result<Path, usize>, String> {
+    let mut unrolling = Unrolling::new(model)?;
+    unrolling.add_state();
+    let init = unrolling.init(0);
+    let mut assumptions = vec![init];
+    let state_ids: Vec<usize> = std::iter::once(trace.start)
+        .chain(trace.steps.iter().map(|(_, state_id)| *state_id))
+        .collect();
+    for (step, state_id) in state_ids.iter().enumerate() {
+        if step > 0 {
+            unrolling.add_step();
+            let mut taken = unrolling.cnf.constant(false);
+            for (label, selector) in unrolling.selectors(step - 1).to_vec() {
+                if label == trace.steps[step - 1].0 {
+                    taken = unrolling.cnf.or(taken, selector);
+                }
+            }
+            assumptions.push(taken);
+        }
+        let state = &abstraction.lts.0[*state_id].state;
+        for (predicate, value) in abstraction.predicates.iter().zip(state.vars.iter()) {
+            let lit = unrolling.expr(predicate, step);
+            assumptions.push(if *value { lit } else { !lit });
+        }
+        for (p, location) in state.locations.iter().enumerate() {
+            let at = Formula::At(p, location.clone());
+            assumptions.push(unrolling.formula(|_| None, &at, step)?);
+        }
+        if !unrolling.cnf.solver.solve_with(&assumptions) {
+            return Ok(Err(step));
+        }
+    }
+    Ok(Ok(unrolling.path(state_ids.len())))
+}
+
+/// the new predicates ruling out the step of the trace into the state `failing`, which is
+/// not the initial one: the guards of the transitions it may take, and the weakest
+/// preconditions of the predicates.
+fn refine(model: &Model, abstraction: &Abstraction, trace: &Trace, failing: usize) -> Vec<Expr> {
+    let from = if failing == 1 {
+        trace.start
+    } else {
+        trace.steps[failing - 2].1
+    };
+    let (label, _) = &trace.steps[failing - 1];
+    let locations = &abstraction.lts.0[from].state.locations;
+    let mut refined = vec![];
+    for (p, process) in model.processes.iter().enumerate() {
+        for exec in process.iter().filter(|exec| exec.src == locations[p]) {
+            for trans in exec.transs.iter().filter(|trans| &trans.label == label) {
+                let preconditions = abstraction
+                    .predicates
+                    .iter()
+                    .map(|predicate| predicate.substitute(&trans.updates));
+                for expr in std::iter::once(trans.guard.clone()).chain(preconditions) {
+                    let trivial = matches!(expr, Expr::Const(_));
+                    if !trivial
+                        && !abstraction.predicates.contains(&expr)
+                        && !refined.contains(&expr)
+                    {
+                        refined.push(expr);
+                    }
+                }
+            }
+        }
+    }
+    refined
+}
+
+/// a concrete path following the abstract trace, or the number of steps after which no
+/// concrete path follows it. the concrete states reached by each prefix of the trace are
+/// kept with the index of their predecessor.
+fn replay_process<T: Clone + Hash + Eq>(
+    s0: &State<T>,
+    processes: &[Process<T>],
+    abstraction: &Abstraction<Predicate<T>>,
+    trace: &Trace,
+) -> Result<Path<T>, usize> {
+    let abstract_state = |state: &State<T>| State {
+        vars: abstraction
+            .predicates
+            .iter()
+            .map(|predicate| (predicate.holds)(&state.vars))
+            .collect::<Vec<bool>>(),
+        locations: state.locations.clone(),
+        zone: None,
+    };
+    let mut layers = vec![vec![(s0.clone(), 0)]];
+    for (step, (label, state_id)) in trace.steps.iter().enumerate() {
+        let expected = &abstraction.lts.0[*state_id].state;
+        let mut seen = HashSet::new();
+        let mut layer = vec![];
+        for (parent, (state, _)) in layers[step].iter().enumerate() {
+            for (next_label, _, next) in lts::successors(processes, state) {
+                if &next_label == label
+                    && &abstract_state(&next) == expected
+                    && seen.insert(next.clone())
+                {
+                    layer.push((next, parent));
+                }
+            }
+        }
+        if layer.is_empty() {
+            return Err(step + 1);
+        }
+        layers.push(layer);
+    }
+    let mut states = vec![];
+    let mut index = 0;
+    for layer in layers.iter().rev() {
+        let (state, parent) = &layer[index];
+        states.push(state.clone());
+        index = *parent;
+    }
+    states.reverse();
+    let steps = trace
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(step, (label, _))| (label.clone(), step + 1))
+        .collect();
+    Ok(Path {
+        states,
+        trace: Trace { start: 0, steps },
+    })
+}
+
+/// the new predicates ruling out the step of the trace into the state `failing`: the
+/// guards of the transitions it may take, and the predicates after their actions, each
+/// kept if it splits the abstract states of the reachable concrete ones further.
+fn refine_process<T: Clone + Hash + Eq + 'static>(
+    processes: &[Process<T>],
+    reachable: &[State<T>],
+    abstraction: &Abstraction<Predicate<T>>,
+    trace: &Trace,
+    failing: usize,
+) -> Vec<Predicate<T>> {
+    let from = if failing == 1 {
+        trace.start
+    } else {
+        trace.steps[failing - 2].1
+    };
+    let (label, _) = &trace.steps[failing - 1];
+    let locations = &abstraction.lts.0[from].state.locations;
+    let mut candidates = vec![];
+    for (p, process) in processes.iter().enumerate() {
+        for exec in process.iter().filter(|exec| exec.src == locations[p]) {
+            for trans in exec.transs.iter().filter(|trans| &trans.label == label) {
+                let guard = trans.guard;
+                candidates.push(Predicate::new(&format!("guard of {}", label), guard));
+                for predicate in abstraction.predicates.iter() {
+                    let action = trans.action;
+                    let holds = predicate.holds.clone();
+                    let name = format!("{} after {}", predicate.name, label);
+                    candidates.push(Predicate::new(&name, move |vars: &T| {
+                        holds(&action(vars.clone()))
+                    }));
+                }
+            }
+        }
+    }
+    let blocks = |predicates: &[Predicate<T>]| -> usize {
+        reachable
+            .iter()
+            .map(|state| {
+                let values: Vec<bool> = predicates
+                    .iter()
+                    .map(|predicate| (predicate.holds)(&state.vars))
+                    .collect();
+                (&state.locations, values)
+            })
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    let mut predicates = abstraction.predicates.clone();
+    let mut count = blocks(&predicates);
+    let mut refined = vec![];
+    for candidate in candidates {
+        predicates.push(candidate.clone());
+        let split = blocks(&predicates);
+        if split > count {
+            count = split;
+            refined.push(candidate);
+        } else {
+            predicates.pop();
+        }
+    }
+    refined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        process::{self, Location},
+        symbolic::{ExecUnit, Trans, VarDecl},
+    };
+
+    /// sets `x` to 1 on `a`, then takes `b` to `end` if `x` is `target`.
+    fn model(target: u64) -> Model {
+        let x = || Box::new(Expr::Var(0));
+        let exec = |src: &str, label: &str, dst: &str, guard, updates| ExecUnit {
+            src: Location::new(src),
+            transs: vec![Trans {
+                label: Label::new(label),
+                dst: Location::new(dst),
+                guard,
+                updates,
+            }],
+        };
+        Model {
+            vars: vec![VarDecl {
+                name: "x".to_string(),
+                bits: 2,
+                init: 0,
+            }],
+            processes: vec![vec![
+                exec(
+                    "start",
+                    "a",
+                    "mid",
+                    Expr::Const(1),
+                    vec![(0, Expr::Const(1))],
+                ),
+                exec(
+                    "mid",
+                    "b",
+                    "end",
+                    Expr::Eq(x(), Box::new(Expr::Const(target))),
+                    vec![],
+                ),
+            ]],
+        }
+    }
+
+    fn never_end() -> Formula {
+        Formula::Not(Box::new(Formula::At(0, Location::new("end"))))
+    }
+
+    #[test]
+    fn spurious_paths_are_refined_away() {
+        match check(&model(2), |_| None, &never_end(), vec![], 3).unwrap() {
+            Outcome::Holds(abstraction) => {
+                let x_is_2 = Expr::Eq(Box::new(Expr::Var(0)), Box::new(Expr::Const(2)));
+                assert!(abstraction.predicates.contains(&x_is_2));
+            }
+            outcome => panic!("{:?}", outcome),
+        }
+        let outcome = check(&model(2), |_| None, &never_end(), vec![], 0).unwrap();
+        assert!(matches!(outcome, Outcome::Unknown(_)));
+    }
+
+    #[test]
+    fn real_paths_are_concretized() {
+        match check(&model(1), |_| None, &never_end(), vec![], 3).unwrap() {
+            Outcome::Violated(path) => {
+                assert_eq!(path.trace.to_string(), "0 -a-> 1 -b-> 2");
+                assert_eq!(path.states[2].vars, vec![1]);
+            }
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+
+    /// a counter incremented up to 3 at a single location.
+    fn counter() -> Vec<Process<u8>> {
+        vec![vec![process::ExecUnit {
+            src: Location::new("L"),
+            invariant: vec![],
+            transs: vec![process::Trans {
+                label: Label::new("inc"),
+                dst: Location::new("L"),
+                guard: |x| *x < 3,
+                action: |x| x + 1,
+                clock_guard: vec![],
+                resets: vec![],
+            }],
+        }]]
+    }
+
+    fn equals(p: &Prop, x: &u8) -> bool {
+        p.strip_prefix("x==").and_then(|n| n.parse().ok()) == Some(*x)
+    }
+
+    fn never(value: u8) -> Formula {
+        Formula::Not(Box::new(Formula::Prop(format!("x=={}", value))))
+    }
+
+    #[test]
+    fn process_paths_are_refined_until_concrete() {
+        // the counter below 3 is one abstract state, where `inc` seems to reach 3 at once
+        match check_process(0, &counter(), equals, &never(3), vec![], 3).unwrap() {
+            Outcome::Violated(path) => {
+                assert_eq!(path.trace.to_string(), "0 -inc-> 1 -inc-> 2 -inc-> 3");
+                let values: Vec<u8> = path.states.iter().map(|state| state.vars).collect();
+                assert_eq!(values, vec![0, 1, 2, 3]);
+            }
+            outcome => panic!("{:?}", outcome),
+        }
+        match check_process(0, &counter(), equals, &never(3), vec![], 1).unwrap() {
+            Outcome::Unknown(abstraction) => {
+                let names: Vec<&str> = abstraction
+                    .predicates
+                    .iter()
+                    .map(|predicate| predicate.name.as_str())
+                    .collect();
+                assert_eq!(names, vec!["x==3", "x==3 after inc"]);
+            }
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn process_invariants_hold_on_the_abstraction() {
+        match check_process(0, &counter(), equals, &never(4), vec![], 0).unwrap() {
+            Outcome::Holds(abstraction) => assert_eq!(abstraction.lts.0.len(), 1),
+            outcome => panic!("{:?}", outcome),
+        }
+        let odd = Predicate::new("odd", |x: &u8| x % 2 == 1);
+        match check_process(0, &counter(), equals, &never(4), vec![odd], 0).unwrap() {
+            Outcome::Holds(abstraction) => assert_eq!(abstraction.lts.0.len(), 2),
+            outcome => panic!("{:?}", outcome),
+        }
+    }
+}
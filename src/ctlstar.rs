@@ -0,0 +1,161 @@
+use std::hash::Hash;
+
+use crate::{
+    formula::{Formula, Prop},
+    ltl::{self, Ltl, Product},
+    lts::Lts,
+    mark::{self, Deadlock, Options},
+};
+
+/// A CTL* state formula. Path formulas are ltl formulas over state formulas.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum StateFormula {
+    /// a CTL formula, marked by `mark::make_marks_with`.
+    Ctl(Formula),
+    Not(Box<StateFormula>),
+    And(Box<StateFormula>, Box<StateFormula>),
+    Or(Box<StateFormula>, Box<StateFormula>),
+    Impl(Box<StateFormula>, Box<StateFormula>),
+    /// some path from the state satisfies the path formula.
+    E(Box<Ltl<StateFormula>>),
+    /// every path from the state satisfies the path formula.
+    A(Box<Ltl<StateFormula>>),
+}
+
+/// Evaluates `spec` on every state of the lts.
+///
+/// state subformulas are evaluated bottom-up; `E` checks the emptiness of the product
+/// of the lts with a Büchi automaton for its path formula, started from each state.
+/// under `Deadlock::Finite` a path reaching a terminal state stutters there forever;
+/// under `Deadlock::Fixpoint` only the infinite paths count.
+pub fn check<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    spec: &StateFormula,
+    deadlock: Deadlock,
+) -> Result<Vec<bool>, String> {
+    let mut looped = mark::apply_deadlock(lts, deadlock)?;
+    let lts = looped.as_mut().unwrap_or(lts);
+    eval(lts, prop_valuate, spec, deadlock)
+}
+
+fn eval<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    f: &StateFormula,
+    deadlock: Deadlock,
+) -> Result<Vec<bool>, String> {
+    use StateFormula::*;
+    let binary = |lhs: Vec<bool>, rhs: Vec<bool>, op: fn(bool, bool) -> bool| -> Vec<bool> {
+        lhs.into_iter().zip(rhs).map(|(l, r)| op(l, r)).collect()
+    };
+    Ok(match f {
+        Ctl(f) => {
+            mark::reject_epistemic(f)?;
+            let options = Options {
+                deadlock,
+                ..Default::default()
+            };
+            let marks = mark::make_marks_with(lts, prop_valuate, f.clone(), &options)?;
+            (0..lts.0.len()).map(|id| marks.holds_at(id)).collect()
+        }
+        Not(f) => eval(lts, prop_valuate, f, deadlock)?
+            .into_iter()
+            .map(|b| !b)
+            .collect(),
+        And(f, g) => binary(
+            eval(lts, prop_valuate, f, deadlock)?,
+            eval(lts, prop_valuate, g, deadlock)?,
+            |l, r| l && r,
+        ),
+        Or(f, g) => binary(
+            eval(lts, prop_valuate, f, deadlock)?,
+            eval(lts, prop_valuate, g, deadlock)?,
+            |l, r| l || r,
+        ),
+        Impl(f, g) => binary(
+            eval(lts, prop_valuate, f, deadlock)?,
+            eval(lts, prop_valuate, g, deadlock)?,
+            |l, r| !l || r,
+        ),
+        E(path) => {
+            let (atoms, gba) = ltl::translate(path);
+            let mut truth = vec![];
+            for atom in atoms.iter() {
+                truth.push(eval(lts, prop_valuate, atom, deadlock)?);
+            }
+            let starts: Vec<_> = (0..lts.0.len()).collect();
+            let product = Product::new(lts, &gba, &truth, &starts, deadlock);
+            let accepting = product.accepting_states(&gba);
+            let mut result = vec![false; lts.0.len()];
+            for root in product.roots.iter() {
+                if accepting[*root] {
+                    result[product.states[*root].0] = true;
+                }
+            }
+            result
+        }
+        A(path) => {
+            let negated = E(Box::new(Ltl::Not(path.clone())));
+            eval(lts, prop_valuate, &negated, deadlock)?
+                .into_iter()
+                .map(|b| !b)
+                .collect()
+        }
+    })
+}
+
+use std::fmt;
+impl fmt::Display for StateFormula {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use StateFormula::*;
+        match self {
+            Ctl(ref f) => write!(fmt, "{}", f),
+            Not(ref f) => write!(fmt, "(not {})", f),
+            And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
+            Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
+            Impl(ref lhs, ref rhs) => write!(fmt, "(impl {} {})", lhs, rhs),
+            E(ref f) => write!(fmt, "(E {})", f),
+            A(ref f) => write!(fmt, "(A {})", f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::tests::lts, process::Location};
+
+    #[test]
+    fn path_quantifiers_over_nested_temporal_operators() {
+        // 0 -a-> 1 -c-> 0, or 0 -b-> 2 staying at `A` forever
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![("c", 0, Some(0))],
+            vec![("d", 2, Some(0))],
+        ]);
+        let at = |location| {
+            Box::new(Ltl::Atom(StateFormula::Ctl(Formula::At(
+                0,
+                Location::new(location),
+            ))))
+        };
+        // `E G F at B`, which is no CTL formula
+        let egf = StateFormula::E(Box::new(Ltl::G(Box::new(Ltl::F(at("B"))))));
+        assert_eq!(
+            check(&mut lts, |_, _| false, &egf, Deadlock::Fixpoint),
+            Ok(vec![true, true, false])
+        );
+        // `A F G at A`
+        let afg = StateFormula::A(Box::new(Ltl::F(Box::new(Ltl::G(at("A"))))));
+        assert_eq!(
+            check(&mut lts, |_, _| false, &afg, Deadlock::Fixpoint),
+            Ok(vec![false, false, true])
+        );
+        let either = StateFormula::Or(Box::new(egf), Box::new(afg));
+        assert_eq!(
+            check(&mut lts, |_, _| false, &either, Deadlock::Fixpoint),
+            Ok(vec![true, true, true])
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    lts::{self, Lts, State, StateId, STUTTER},
+    process::Label,
+};
+
+/// How the process to move next is picked among the enabled ones.
+pub enum Scheduler<T> {
+    /// every enabled process is equally likely to move.
+    Uniform,
+    /// the function picks one of the enabled processes, given by their indices.
+    Fixed(fn(&State<T>, &[usize]) -> usize),
+}
+
+/// A discrete-time Markov chain over the states of an `Lts`:
+/// the transitions from each state with their probabilities.
+#[derive(Debug, Clone)]
+pub struct Dtmc(pub Vec<Vec<(Label, StateId, f64)>>);
+
+impl Dtmc {
+    /// resolves the interleaving of the lts with the scheduler, then the choice of the
+    /// moving process among its enabled transitions by their weights, as given to
+    /// `Lts::weighted_composition`.
+    /// terminal states loop on themselves with probability 1.
+    pub fn new<T>(lts: &Lts<T>, scheduler: &Scheduler<T>) -> Result<Dtmc, String> {
+        let mut chain = vec![];
+        for (state_id, trans) in lts.0.iter().enumerate() {
+            let enabled = enabled_processes(trans);
+            if enabled.is_empty() {
+                chain.push(vec![(Label::new(STUTTER), state_id, 1.0)]);
+                continue;
+            }
+            let chosen: Vec<(usize, f64)> = match scheduler {
+                Scheduler::Uniform => {
+                    let p = 1.0 / enabled.len() as f64;
+                    enabled.iter().map(|process| (*process, p)).collect()
+                }
+                Scheduler::Fixed(pick) => {
+                    let process = pick(&trans.state, &enabled);
+                    if !enabled.contains(&process) {
+                        return Err(format!(
+                            "scheduler picked process {} not enabled at state {}",
+                            process, state_id
+                        ));
+                    }
+                    vec![(process, 1.0)]
+                }
+            };
+            let mut succs = vec![];
+            for (process, p) in chosen {
+                for (label, next_id, q) in distribution(trans, state_id, process)? {
+                    succs.push((label, next_id, p * q));
+                }
+            }
+            chain.push(succs);
+        }
+        Ok(Dtmc(chain))
+    }
+
+    /// the states entering each state with a positive probability.
+    pub fn predecessors(&self) -> Vec<Vec<StateId>> {
+        let mut preds = vec![vec![]; self.0.len()];
+        for (state_id, succs) in self.0.iter().enumerate() {
+            for (_, next_id, p) in succs.iter() {
+                if *p > 0.0 && !preds[*next_id].contains(&state_id) {
+                    preds[*next_id].push(state_id);
+                }
+            }
+        }
+        preds
+    }
+}
+
+/// the processes having a transition from the state, stuttering excluded.
+pub(crate) fn enabled_processes<T>(trans: &lts::Trans<T>) -> Vec<usize> {
+    let mut enabled = vec![];
+    for process in trans.steps.iter().filter_map(|step| step.process) {
+        if !enabled.contains(&process) {
+            enabled.push(process);
+        }
+    }
+    enabled
+}
+
+/// the transitions of the process from the state, with their normalized weights.
+pub(crate) fn distribution<T>(
+    trans: &lts::Trans<T>,
+    state_id: StateId,
+    process: usize,
+) -> Result<Vec<(Label, StateId, f64)>, String> {
+    let mut total = 0.0;
+    for weight in trans
+        .steps
+        .iter()
+        .filter(|step| step.process == Some(process))
+        .map(|step| step.weight)
+    {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!(
+                "process {} has weight {} at state {}",
+                process, weight, state_id
+            ));
+        }
+        total += weight;
+    }
+    if total <= 0.0 {
+        return Err(format!(
+            "process {} has no positive weight at state {}",
+            process, state_id
+        ));
+    }
+    let mut succs = vec![];
+    for step in trans.steps.iter() {
+        if step.process == Some(process) {
+            succs.push((step.label.clone(), step.dst, step.weight / total));
+        }
+    }
+    Ok(succs)
+}
+
+/// solves `a x = b` by Gaussian elimination with partial pivoting. `a` must be regular.
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+            .unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            if factor == 0.0 {
+                continue;
+            }
+            let (upper, lower) = a.split_at_mut(row);
+            for (x, y) in lower[0][col..].iter_mut().zip(upper[col][col..].iter()) {
+                *x -= factor * y;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x
+}
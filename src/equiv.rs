@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use crate::{
+    bisim::numbered,
+    formula::{Actions, Formula},
+    lts::{Lts, StateId, Step, TAU},
+    process::Label,
+    refine::{self, Semantics, Violation},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Equivalence {
+    /// strong bisimilarity: each transition is matched by one with the same label.
+    Strong,
+    /// weak bisimilarity: each transition is matched by one with the same label, `TAU`
+    /// steps allowed before and after, and a `TAU` transition also by no step at all.
+    Weak,
+    /// the same traces of visible labels.
+    Traces,
+}
+
+/// Why two lts are not equivalent.
+#[derive(Debug, Clone)]
+pub enum Difference {
+    /// a Hennessy-Milner formula holding in the initial state of the left lts but not in
+    /// that of the right one. for `Equivalence::Weak` its diamonds allow `TAU` steps around
+    /// the labelled one, through `EUa`.
+    Formula(Formula),
+    /// a shortest trace of visible labels of one lts only, the left one if `true`.
+    Trace(Vec<Label>, bool),
+}
+
+/// Checks whether the initial states of the two lts are equivalent, e.g. a model before and
+/// after a refactoring, and returns a difference otherwise. only the labels are compared,
+/// and `STUTTER` loops are ignored.
+///
+/// bisimilarity is decided by partition refinement on the disjoint union of the lts, saturated
+/// by the `TAU` steps for the weak one; the partitions of every round are kept to build the
+/// formula from the round separating the states. trace equivalence is refinement both ways.
+pub fn check<T, U>(left: &Lts<T>, right: &Lts<U>, equivalence: Equivalence) -> Option<Difference> {
+    if equivalence == Equivalence::Traces {
+        let left_only = refine::check(right, left, Semantics::Traces).map(|v| (v, true));
+        let right_only = refine::check(left, right, Semantics::Traces).map(|v| (v, false));
+        let shortest = match (left_only, right_only) {
+            (Some(l), Some(r)) => Some(if trace(&r.0).len() < trace(&l.0).len() {
+                r
+            } else {
+                l
+            }),
+            (l, r) => l.or(r),
+        };
+        return shortest
+            .map(|(violation, is_left)| Difference::Trace(trace(&violation).to_vec(), is_left));
+    }
+    let mut steps = union(left, right);
+    if equivalence == Equivalence::Weak {
+        steps = saturate(&steps);
+    }
+    let rounds = partitions(&steps);
+    let last = rounds.last().unwrap();
+    if last[0] == last[left.0.len()] {
+        return None;
+    }
+    let mut distinguisher = Distinguisher {
+        steps: &steps,
+        rounds: &rounds,
+        weak: equivalence == Equivalence::Weak,
+        memo: HashMap::new(),
+    };
+    Some(Difference::Formula(distinguisher.formula(0, left.0.len())))
+}
+
+fn trace(violation: &Violation) -> &[Label] {
+    match violation {
+        Violation::Trace(trace) => trace,
+        Violation::Failure { trace, .. } => trace,
+        Violation::Divergence(trace) => trace,
+    }
+}
+
+/// the labelled steps of the states of both lts, those of the right one numbered after the
+/// left ones.
+fn union<T, U>(left: &Lts<T>, right: &Lts<U>) -> Vec<Vec<(Label, StateId)>> {
+    let offset = left.0.len();
+    let steps = |steps: &[Step], offset: usize| {
+        steps
+            .iter()
+            .filter(|step| step.process.is_some())
+            .map(|step| (step.label.clone(), step.dst + offset))
+            .collect::<Vec<_>>()
+    };
+    left.0
+        .iter()
+        .map(|trans| steps(&trans.steps, 0))
+        .chain(right.0.iter().map(|trans| steps(&trans.steps, offset)))
+        .collect()
+}
+
+/// the weak steps: `TAU` to every state reachable by `TAU` steps, the state itself included,
+/// and a visible label to every state reachable by `TAU` steps around it.
+fn saturate(steps: &[Vec<(Label, StateId)>]) -> Vec<Vec<(Label, StateId)>> {
+    let closures: Vec<Vec<StateId>> = (0..steps.len())
+        .map(|state_id| {
+            let mut closure = vec![state_id];
+            let mut i = 0;
+            while i < closure.len() {
+                for (label, next_id) in steps[closure[i]].iter() {
+                    if label.0 == TAU && !closure.contains(next_id) {
+                        closure.push(*next_id);
+                    }
+                }
+                i += 1;
+            }
+            closure
+        })
+        .collect();
+    closures
+        .iter()
+        .map(|closure| {
+            let tau = Label::new(TAU);
+            let mut weak: Vec<(Label, StateId)> =
+                closure.iter().map(|id| (tau.clone(), *id)).collect();
+            for (label, next_id) in closure.iter().flat_map(|id| steps[*id].iter()) {
+                if label.0 != TAU {
+                    for after in closures[*next_id].iter() {
+                        let step = (label.clone(), *after);
+                        if !weak.contains(&step) {
+                            weak.push(step);
+                        }
+                    }
+                }
+            }
+            weak
+        })
+        .collect()
+}
+
+/// the partitions of each round of the refinement, from a single block until stable.
+fn partitions(steps: &[Vec<(Label, StateId)>]) -> Vec<Vec<usize>> {
+    let mut rounds = vec![vec![0; steps.len()]];
+    loop {
+        let blocks = rounds.last().unwrap();
+        let refined = numbered(
+            (0..steps.len()).map(|state_id| (blocks[state_id], signature(steps, blocks, state_id))),
+        );
+        if refined.iter().max() == blocks.iter().max() {
+            return rounds;
+        }
+        rounds.push(refined);
+    }
+}
+
+/// the labels and the blocks of the steps of the state.
+fn signature<'a>(
+    steps: &'a [Vec<(Label, StateId)>],
+    blocks: &[usize],
+    state_id: StateId,
+) -> Vec<(&'a str, usize)> {
+    let mut signature: Vec<(&str, usize)> = steps[state_id]
+        .iter()
+        .map(|(label, next_id)| (label.0.as_str(), blocks[*next_id]))
+        .collect();
+    signature.sort();
+    signature.dedup();
+    signature
+}
+
+struct Distinguisher<'a> {
+    steps: &'a [Vec<(Label, StateId)>],
+    rounds: &'a [Vec<usize>],
+    weak: bool,
+    memo: HashMap<(StateId, StateId), Formula>,
+}
+
+impl<'a> Distinguisher<'a> {
+    /// a formula holding in `s` but not in `t`, which some round separates.
+    fn formula(&mut self, s: StateId, t: StateId) -> Formula {
+        if let Some(f) = self.memo.get(&(s, t)) {
+            return f.clone();
+        }
+        // the round before the first one separating them tells them apart by a step
+        let round = self.rounds.iter().position(|b| b[s] != b[t]).unwrap() - 1;
+        let blocks = &self.rounds[round];
+        let theirs = signature(self.steps, blocks, t);
+        let step = self.steps[s]
+            .iter()
+            .find(|(label, next_id)| !theirs.contains(&(label.0.as_str(), blocks[*next_id])));
+        let f = match step {
+            Some((label, next_id)) => {
+                let mut conjuncts: Vec<Formula> = vec![];
+                for (l, other_id) in self.steps[t].iter() {
+                    if l == label {
+                        let f = self.formula(*next_id, *other_id);
+                        if !conjuncts.contains(&f) {
+                            conjuncts.push(f);
+                        }
+                    }
+                }
+                let f = conjuncts
+                    .into_iter()
+                    .reduce(|f, g| Formula::And(Box::new(f), Box::new(g)))
+                    .unwrap_or(Formula::True);
+                self.diamond(label, f)
+            }
+            None => Formula::Not(Box::new(self.formula(t, s))),
+        };
+        self.memo.insert((s, t), f.clone());
+        f
+    }
+
+    /// `<a> f`, or `<<a>> f` with the `TAU` steps around it if weak.
+    fn diamond(&self, label: &Label, f: Formula) -> Formula {
+        if !self.weak {
+            return Formula::Diamond(Actions::Of(vec![label.clone()]), Box::new(f));
+        }
+        let tau = || Actions::Of(vec![Label::new(TAU)]);
+        // `TAU` steps, possibly none, into `f`
+        let after = if f == Formula::True {
+            f
+        } else {
+            Formula::Or(
+                Box::new(f.clone()),
+                Box::new(Formula::EUa(
+                    Box::new(Formula::True),
+                    tau(),
+                    tau(),
+                    Box::new(f),
+                )),
+            )
+        };
+        if label.0 == TAU {
+            after
+        } else {
+            Formula::EUa(
+                Box::new(Formula::True),
+                tau(),
+                Actions::Of(vec![label.clone()]),
+                Box::new(after),
+            )
+        }
+    }
+}
+
+use std::fmt;
+impl fmt::Display for Difference {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Formula(f) => write!(fmt, "{}", f),
+            Difference::Trace(trace, is_left) => {
+                let labels: Vec<String> = trace.iter().map(|label| label.to_string()).collect();
+                let side = if *is_left { "left" } else { "right" };
+                write!(fmt, "trace <{}> of the {} only", labels.join(" "), side)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::tests::lts, mark};
+
+    /// whether the formula of the difference holds in the initial state of each lts.
+    fn holds(difference: &Difference, left: Lts<i32>, right: Lts<i32>) -> (bool, bool) {
+        let f = match difference {
+            Difference::Formula(f) => f,
+            Difference::Trace(..) => panic!("not a formula"),
+        };
+        let holds =
+            |mut lts: Lts<i32>| mark::satisfying(&mut lts, |_, _| false, f.clone()).unwrap()[0];
+        (holds(left), holds(right))
+    }
+
+    #[test]
+    fn strong_formula_tells_branching_apart() {
+        // a.(b + c) against a.b + a.c
+        let left = lts(vec![
+            vec![("a", 1, Some(0))],
+            vec![("b", 2, Some(0)), ("c", 2, Some(0))],
+            vec![],
+        ]);
+        let right = lts(vec![
+            vec![("a", 1, Some(0)), ("a", 3, Some(0))],
+            vec![("b", 2, Some(0))],
+            vec![],
+            vec![("c", 2, Some(0))],
+        ]);
+        assert_eq!(
+            check(&left, &right, Equivalence::Traces).map(|d| d.to_string()),
+            None
+        );
+        let difference = check(&left, &right, Equivalence::Strong).unwrap();
+        assert_eq!(holds(&difference, left, right), (true, false));
+        assert_eq!(
+            difference.to_string(),
+            "(<{a}> (and (<{c}> true) (<{b}> true)))"
+        );
+    }
+
+    #[test]
+    fn weak_ignores_tau_but_not_lost_choices() {
+        let left = lts(vec![
+            vec![(TAU, 2, Some(0))],
+            vec![],
+            vec![("a", 1, Some(0))],
+        ]);
+        let right = lts(vec![vec![("a", 1, Some(0))], vec![]]);
+        assert!(check(&left, &right, Equivalence::Strong).is_some());
+        assert!(check(&left, &right, Equivalence::Weak).is_none());
+        // the `TAU` step of `left` loses the `b`
+        let left = lts(vec![
+            vec![(TAU, 2, Some(0)), ("b", 1, Some(0))],
+            vec![],
+            vec![("a", 1, Some(0))],
+        ]);
+        let right = lts(vec![vec![("a", 1, Some(0)), ("b", 1, Some(0))], vec![]]);
+        let difference = check(&left, &right, Equivalence::Weak).unwrap();
+        assert_eq!(holds(&difference, left, right), (true, false));
+        assert_eq!(
+            difference.to_string(),
+            "(or (not (EU true {tau} {b} true)) (EU true {tau} {tau} (not (EU true {tau} {b} true))))"
+        );
+    }
+
+    #[test]
+    fn trace_difference_is_shortest() {
+        let left = lts(vec![
+            vec![("a", 1, Some(0)), (TAU, 2, Some(0))],
+            vec![("b", 3, Some(0))],
+            vec![(TAU, 1, Some(0))],
+            vec![],
+        ]);
+        let right = lts(vec![vec![("a", 0, Some(0))]]);
+        let difference = check(&left, &right, Equivalence::Traces).unwrap();
+        assert_eq!(difference.to_string(), "trace <b> of the left only");
+    }
+}
@@ -1,10 +1,38 @@
 use bimap::BiMap;
 
+use crate::process::{Label, Location};
+
 pub type Prop = String;
 
+/// a set of transition labels, used to restrict the action-based operators.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Actions {
+    Any,
+    Of(Vec<Label>),
+    Except(Vec<Label>),
+}
+
+impl Actions {
+    pub fn contains(&self, label: &Label) -> bool {
+        match self {
+            Actions::Any => true,
+            Actions::Of(labels) => labels.contains(label),
+            Actions::Except(labels) => !labels.contains(label),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Formula {
+    True,
+    False,
     Prop(Prop),
+    /// the `usize`-th process is at the location.
+    At(usize, Location),
+    /// no process can move from the state, stuttering loops aside.
+    Deadlock,
+    /// some process has an outgoing transition with the label.
+    Enabled(Label),
     Not(Box<Formula>),
     And(Box<Formula>, Box<Formula>),
     Or(Box<Formula>, Box<Formula>),
@@ -12,6 +40,41 @@ pub enum Formula {
     EX(Box<Formula>),
     EU(Box<Formula>, Box<Formula>),
     EG(Box<Formula>),
+    /// `<a> f`: some transition with a label in `a` leads to a state satisfying `f`.
+    /// the box modality `[a] f` is `Not(Diamond(a, Not(f)))`.
+    Diamond(Actions, Box<Formula>),
+    /// `E[f {a} U {b} g]`: there is a path of `a`-transitions through `f`-states
+    /// followed by a single `b`-transition into a `g`-state.
+    EUa(Box<Formula>, Actions, Actions, Box<Formula>),
+    /// `EG {a} f`: there is a maximal path of `a`-transitions through `f`-states. unless
+    /// under `mark::Deadlock::Fixpoint`, it may end in a state without `a`-transitions.
+    EGa(Actions, Box<Formula>),
+    // past-time operators quantify over the paths from the initial state 0 which
+    // end in the current state.
+    /// some such path has a previous state, which satisfies `f`.
+    EY(Box<Formula>),
+    /// `f` held at some point of some such path.
+    EO(Box<Formula>),
+    /// `f` held all along some such path.
+    EH(Box<Formula>),
+    /// on some such path `g` held at some point, and `f` has held ever since.
+    ES(Box<Formula>, Box<Formula>),
+    /// `E[f U[<=k] g]`: on some path `g` holds within `k` steps, and `f` before that.
+    /// `EF[<=k] g` is `EUb(k, True, g)`, and `AG[<=k] f` is its dual.
+    EUb(usize, Box<Formula>, Box<Formula>),
+    /// `A[f U[<=k] g]`: on every path `g` holds within `k` steps, and `f` before that.
+    AUb(usize, Box<Formula>, Box<Formula>),
+    /// `EG[<=k] f`: some path keeps `f` for its first `k` steps.
+    EGb(usize, Box<Formula>),
+    // epistemic operators relate the states a process cannot tell apart,
+    // by the observations given in `mark::Options`.
+    /// `K_i f`: process `i` knows `f`, which holds in every state it observes alike.
+    K(usize, Box<Formula>),
+    /// `E_G f`: every process of the group knows `f`.
+    EK(Vec<usize>, Box<Formula>),
+    /// `C_G f`: `f` is common knowledge in the group: everyone knows it,
+    /// everyone knows that everyone knows it, and so on.
+    CK(Vec<usize>, Box<Formula>),
 }
 
 impl Formula {
@@ -19,61 +82,146 @@ impl Formula {
         fn unfold_impl(f: Formula, mut acc: BiMap<usize, Formula>) -> BiMap<usize, Formula> {
             use Formula::*;
             match f {
-                Prop(p) => {
-                    acc.insert(acc.len(), Prop(p));
+                True | False | Prop(_) | At(_, _) | Deadlock | Enabled(_) => {
+                    insert_new(&mut acc, f);
                     acc
                 }
                 Not(box f) => {
                     let mut acc = unfold_impl(f.clone(), acc);
-                    acc.insert(acc.len(), Not(Box::new(f)));
+                    insert_new(&mut acc, Not(Box::new(f)));
                     acc
                 }
                 And(box f1, box f2) => {
                     let acc = unfold_impl(f1.clone(), acc);
                     let mut acc = unfold_impl(f2.clone(), acc);
-                    acc.insert(acc.len(), And(Box::new(f1), Box::new(f2)));
+                    insert_new(&mut acc, And(Box::new(f1), Box::new(f2)));
                     acc
                 }
                 Or(box f1, box f2) => {
                     let acc = unfold_impl(f1.clone(), acc);
                     let mut acc = unfold_impl(f2.clone(), acc);
-                    acc.insert(acc.len(), Or(Box::new(f1), Box::new(f2)));
+                    insert_new(&mut acc, Or(Box::new(f1), Box::new(f2)));
                     acc
                 }
                 Impl(box f1, box f2) => {
                     let acc = unfold_impl(f1.clone(), acc);
                     let mut acc = unfold_impl(f2.clone(), acc);
-                    acc.insert(acc.len(), Impl(Box::new(f1), Box::new(f2)));
+                    insert_new(&mut acc, Impl(Box::new(f1), Box::new(f2)));
                     acc
                 }
                 EX(box f) => {
                     let mut acc = unfold_impl(f.clone(), acc);
-                    acc.insert(acc.len(), EX(Box::new(f)));
+                    insert_new(&mut acc, EX(Box::new(f)));
                     acc
                 }
                 EU(box f1, box f2) => {
                     let acc = unfold_impl(f1.clone(), acc);
                     let mut acc = unfold_impl(f2.clone(), acc);
-                    acc.insert(acc.len(), EU(Box::new(f1), Box::new(f2)));
+                    insert_new(&mut acc, EU(Box::new(f1), Box::new(f2)));
                     acc
                 }
                 EG(box f) => {
                     let mut acc = unfold_impl(f.clone(), acc);
-                    acc.insert(acc.len(), EG(Box::new(f)));
+                    insert_new(&mut acc, EG(Box::new(f)));
+                    acc
+                }
+                Diamond(a, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, Diamond(a, Box::new(f)));
+                    acc
+                }
+                EUa(box f1, a, b, box f2) => {
+                    let acc = unfold_impl(f1.clone(), acc);
+                    let mut acc = unfold_impl(f2.clone(), acc);
+                    insert_new(&mut acc, EUa(Box::new(f1), a, b, Box::new(f2)));
+                    acc
+                }
+                EGa(a, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EGa(a, Box::new(f)));
+                    acc
+                }
+                EY(box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EY(Box::new(f)));
+                    acc
+                }
+                EO(box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EO(Box::new(f)));
+                    acc
+                }
+                EH(box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EH(Box::new(f)));
+                    acc
+                }
+                ES(box f1, box f2) => {
+                    let acc = unfold_impl(f1.clone(), acc);
+                    let mut acc = unfold_impl(f2.clone(), acc);
+                    insert_new(&mut acc, ES(Box::new(f1), Box::new(f2)));
+                    acc
+                }
+                EUb(k, box f1, box f2) => {
+                    let acc = unfold_impl(f1.clone(), acc);
+                    let mut acc = unfold_impl(f2.clone(), acc);
+                    insert_new(&mut acc, EUb(k, Box::new(f1), Box::new(f2)));
+                    acc
+                }
+                AUb(k, box f1, box f2) => {
+                    let acc = unfold_impl(f1.clone(), acc);
+                    let mut acc = unfold_impl(f2.clone(), acc);
+                    insert_new(&mut acc, AUb(k, Box::new(f1), Box::new(f2)));
+                    acc
+                }
+                EGb(k, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EGb(k, Box::new(f)));
+                    acc
+                }
+                K(i, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, K(i, Box::new(f)));
+                    acc
+                }
+                EK(group, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, EK(group, Box::new(f)));
+                    acc
+                }
+                CK(group, box f) => {
+                    let mut acc = unfold_impl(f.clone(), acc);
+                    insert_new(&mut acc, CK(group, Box::new(f)));
                     acc
                 }
             }
         }
+        // a subformula occurring several times is numbered once, at its first occurrence
+        fn insert_new(acc: &mut BiMap<usize, Formula>, f: Formula) {
+            if !acc.contains_right(&f) {
+                acc.insert(acc.len(), f);
+            }
+        }
         unfold_impl(self, BiMap::new())
     }
 }
 
 use std::fmt;
+fn group_fmt(group: &[usize]) -> String {
+    let indices: Vec<String> = group.iter().map(|i| i.to_string()).collect();
+    format!("{{{}}}", indices.join(","))
+}
+
 impl fmt::Display for Formula {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         use Formula::*;
         match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
             Prop(ref p) => write!(fmt, "{}", p),
+            At(ref i, ref loc) => write!(fmt, "(at {} {})", i, loc),
+            Deadlock => write!(fmt, "deadlock"),
+            Enabled(ref label) => write!(fmt, "(enabled {})", label),
             Not(ref f) => write!(fmt, "(not {})", f),
             And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
             Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
@@ -81,6 +229,31 @@ impl fmt::Display for Formula {
             EX(ref f) => write!(fmt, "(EX {})", f),
             EU(ref f, ref g) => write!(fmt, "(EU {} {})", f, g),
             EG(ref f) => write!(fmt, "(EG {})", f),
+            Diamond(ref a, ref f) => write!(fmt, "(<{}> {})", a, f),
+            EUa(ref f, ref a, ref b, ref g) => write!(fmt, "(EU {} {} {} {})", f, a, b, g),
+            EGa(ref a, ref f) => write!(fmt, "(EG {} {})", a, f),
+            EY(ref f) => write!(fmt, "(EY {})", f),
+            EO(ref f) => write!(fmt, "(EO {})", f),
+            EH(ref f) => write!(fmt, "(EH {})", f),
+            ES(ref f, ref g) => write!(fmt, "(ES {} {})", f, g),
+            EUb(ref k, ref f, ref g) => write!(fmt, "(EU<={} {} {})", k, f, g),
+            AUb(ref k, ref f, ref g) => write!(fmt, "(AU<={} {} {})", k, f, g),
+            EGb(ref k, ref f) => write!(fmt, "(EG<={} {})", k, f),
+            K(ref i, ref f) => write!(fmt, "(K {} {})", i, f),
+            EK(ref group, ref f) => write!(fmt, "(EK {} {})", group_fmt(group), f),
+            CK(ref group, ref f) => write!(fmt, "(CK {} {})", group_fmt(group), f),
         }
     }
 }
+
+impl fmt::Display for Actions {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (prefix, labels) = match self {
+            Actions::Any => return write!(fmt, "{{*}}"),
+            Actions::Of(labels) => ("", labels),
+            Actions::Except(labels) => ("!", labels),
+        };
+        let labels: Vec<_> = labels.iter().map(|l| l.to_string()).collect();
+        write!(fmt, "{}{{{}}}", prefix, labels.join(","))
+    }
+}
@@ -0,0 +1,95 @@
+// graph algorithms shared by the checkers, over nodes numbered `0..n`.
+
+/// strongly connected components reachable from `roots`, in reverse topological order.
+pub(crate) fn sccs(
+    n: usize,
+    roots: &[usize],
+    succs: impl Fn(usize) -> Vec<usize>,
+) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut result = vec![];
+    let mut counter = 0;
+
+    for &root in roots {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // explicit call stack of (node, its successors, next successor to visit)
+        let mut calls = vec![(root, succs(root), 0)];
+        index[root] = counter;
+        lowlink[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((v, vs, pos)) = calls.last_mut() {
+            let v = *v;
+            if let Some(&w) = vs.get(*pos) {
+                *pos += 1;
+                if index[w] == UNVISITED {
+                    index[w] = counter;
+                    lowlink[w] = counter;
+                    counter += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, succs(w), 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+            } else {
+                calls.pop();
+                if let Some((u, _, _)) = calls.last() {
+                    lowlink[*u] = lowlink[*u].min(lowlink[v]);
+                }
+                if lowlink[v] == index[v] {
+                    let mut component = vec![];
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    result.push(component);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// whether a path can stay inside the component forever.
+pub(crate) fn is_nontrivial(component: &[usize], succs: impl Fn(usize) -> Vec<usize>) -> bool {
+    component.len() > 1 || succs(component[0]).contains(&component[0])
+}
+
+/// nodes satisfying `within` which can reach a node of `targets` through such nodes.
+pub(crate) fn backward_reach(
+    n: usize,
+    targets: &[usize],
+    preds: impl Fn(usize) -> Vec<usize>,
+    within: impl Fn(usize) -> bool,
+) -> Vec<bool> {
+    let mut reached = vec![false; n];
+    let mut stack = vec![];
+    for &t in targets {
+        if !reached[t] {
+            reached[t] = true;
+            stack.push(t);
+        }
+    }
+    while let Some(v) = stack.pop() {
+        for u in preds(v) {
+            if !reached[u] && within(u) {
+                reached[u] = true;
+                stack.push(u);
+            }
+        }
+    }
+    reached
+}
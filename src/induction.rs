@@ -0,0 +1,411 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::{
+    bmc::{Path, Unrolling},
+    formula::{Formula, Prop},
+    lts::{State, Trace},
+    process::Label,
+    sat::Lit,
+    symbolic::{Expr, Logic, Model},
+};
+
+/// The `bit`-th bit of a variable, or of the location of a process after the variables,
+/// has the value. a state is encoded as in `symbolic::Symbolic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitLit {
+    pub slot: usize,
+    pub bit: usize,
+    pub value: bool,
+}
+
+/// Why an invariant holds.
+#[derive(Debug, Clone)]
+pub enum Certificate {
+    /// every path of `k` distinct states satisfying the invariant only leads to states
+    /// satisfying it, and it holds along the first `k - 1` steps from the initial state.
+    KInductive(usize),
+    /// an inductive invariant implying the invariant, as a conjunction of clauses:
+    /// it holds in the initial state and is preserved by every transition.
+    Clauses(Vec<Vec<BitLit>>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Verdict {
+    Proved(Certificate),
+    /// a shortest path to a state violating the invariant for k-induction, some path for IC3.
+    Violated(Path),
+    /// the bound was reached.
+    Unknown,
+}
+
+/// Proves `AG invariant` for the state formula `invariant` by k-induction with the
+/// uniqueness of the states along the path, increasing `k` up to `max_k`.
+pub fn k_induction(
+    model: &Model,
+    prop_expr: fn(&Prop) -> Option<Expr>,
+    invariant: &Formula,
+    max_k: usize,
+) -> Result<Verdict, String> {
+    // the base case is bounded model checking
+    let mut base = Unrolling::new(model)?;
+    base.add_state();
+    let init = base.init(0);
+    base.cnf.solver.add_clause(&[init]);
+    // the inductive step starts anywhere
+    let mut step = Unrolling::new(model)?;
+    step.add_state();
+    for k in 0..=max_k {
+        if k > 0 {
+            base.add_step();
+        }
+        let good = base.formula(prop_expr, invariant, k)?;
+        if base.cnf.solver.solve_with(&[!good]) {
+            return Ok(Verdict::Violated(base.path(k + 1)));
+        }
+        let good = step.formula(prop_expr, invariant, k)?;
+        step.cnf.solver.add_clause(&[good]);
+        step.add_step();
+        for other in 0..=k {
+            let differ = distinct(&mut step, other, k + 1);
+            step.cnf.solver.add_clause(&[differ]);
+        }
+        let next = step.formula(prop_expr, invariant, k + 1)?;
+        if !step.cnf.solver.solve_with(&[!next]) {
+            return Ok(Verdict::Proved(Certificate::KInductive(k + 1)));
+        }
+    }
+    Ok(Verdict::Unknown)
+}
+
+/// the literal for the two states being different.
+fn distinct(unrolling: &mut Unrolling, a: usize, b: usize) -> Lit {
+    let pairs: Vec<(Lit, Lit)> = unrolling.states[a]
+        .iter()
+        .flatten()
+        .zip(unrolling.states[b].iter().flatten())
+        .map(|(x, y)| (*x, *y))
+        .collect();
+    let mut differ = unrolling.cnf.constant(false);
+    for (x, y) in pairs {
+        let bit = unrolling.cnf.xor(x, y);
+        differ = unrolling.cnf.or(differ, bit);
+    }
+    differ
+}
+
+/// a set of states, given by the values of some of their bits.
+type Cube = Vec<BitLit>;
+
+/// a state to block at a level of the frames, leading to a state violating the invariant
+/// through the obligation `parent` by the label.
+struct Obligation {
+    state: State<Vec<u64>>,
+    cube: Cube,
+    parent: Option<(usize, Label)>,
+}
+
+/// The IC3/PDR engine: the frames over-approximate the states reachable within each number
+/// of steps, as the cubes blocked at each level; a state formula blocked at a level is also
+/// blocked at the lower ones.
+struct Pdr<'a> {
+    /// a single step from the state 0 to the state 1.
+    unrolling: Unrolling<'a>,
+    /// a transition leads from the state 0 to the state 1. left out of the queries for
+    /// violations, so that deadlocked states are not missed.
+    step: Lit,
+    init: Lit,
+    /// the invariant holds in the state 0.
+    good: Lit,
+    init_bits: Vec<Vec<bool>>,
+    /// the activation literal of the clauses of each level, from 1.
+    levels: Vec<Lit>,
+    frames: Vec<Vec<Cube>>,
+    obligations: Vec<Obligation>,
+}
+
+impl<'a> Pdr<'a> {
+    fn lit(&self, step: usize, bit: &BitLit) -> Lit {
+        let lit = self.unrolling.states[step][bit.slot][bit.bit];
+        if bit.value {
+            lit
+        } else {
+            !lit
+        }
+    }
+
+    /// the assumptions restricting the state 0 to the frame. the frames below the top one
+    /// only hold states satisfying the invariant, once its violations are blocked.
+    fn frame(&self, level: usize) -> Vec<Lit> {
+        if level == 0 {
+            vec![self.init]
+        } else {
+            let mut frame = self.levels[level - 1..].to_vec();
+            frame.push(self.good);
+            frame
+        }
+    }
+
+    /// the cube of the full state `step` in the last satisfying assignment.
+    fn cube(&self, step: usize) -> Cube {
+        let mut cube = vec![];
+        for (slot, bits) in self.unrolling.states[step].iter().enumerate() {
+            for (bit, lit) in bits.iter().enumerate() {
+                let value = self.unrolling.cnf.value(*lit);
+                cube.push(BitLit { slot, bit, value });
+            }
+        }
+        cube
+    }
+
+    fn intersects_init(&self, cube: &Cube) -> bool {
+        cube.iter()
+            .all(|bit| self.init_bits[bit.slot][bit.bit] == bit.value)
+    }
+
+    /// whether the frame at `level` with the cube removed only leads into the cube from
+    /// states outside it: the cube is then inductive relative to the frame.
+    fn is_blocked(&mut self, cube: &Cube, level: usize, outside: bool) -> bool {
+        let mut assumptions = self.frame(level);
+        assumptions.push(self.step);
+        let activation = self.unrolling.cnf.fresh();
+        if outside {
+            let clause: Vec<Lit> = std::iter::once(!activation)
+                .chain(cube.iter().map(|bit| !self.lit(0, bit)))
+                .collect();
+            self.unrolling.cnf.solver.add_clause(&clause);
+            assumptions.push(activation);
+        }
+        assumptions.extend(cube.iter().map(|bit| self.lit(1, bit)));
+        let blocked = !self.unrolling.cnf.solver.solve_with(&assumptions);
+        self.unrolling.cnf.solver.add_clause(&[!activation]);
+        blocked
+    }
+
+    /// drops the bits of the cube which are not needed for it to be blocked, the high
+    /// bits of the last slots first.
+    fn generalize(&mut self, cube: Cube, level: usize) -> Cube {
+        let mut cube = cube;
+        for i in (0..cube.len()).rev() {
+            let mut smaller = cube.clone();
+            smaller.remove(i);
+            if !smaller.is_empty()
+                && !self.intersects_init(&smaller)
+                && self.is_blocked(&smaller, level, true)
+            {
+                cube = smaller;
+            }
+        }
+        cube
+    }
+
+    fn block_at(&mut self, cube: Cube, level: usize) {
+        let clause: Vec<Lit> = std::iter::once(!self.levels[level - 1])
+            .chain(cube.iter().map(|bit| !self.lit(0, bit)))
+            .collect();
+        self.unrolling.cnf.solver.add_clause(&clause);
+        self.frames[level - 1].push(cube);
+    }
+
+    /// blocks the obligation at the level and the states leading to it, or returns the
+    /// index of an initial state's obligation leading to it.
+    fn block(&mut self, obligation: usize, level: usize) -> Option<usize> {
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((level, obligation)));
+        while let Some(Reverse((level, id))) = queue.pop() {
+            let cube = self.obligations[id].cube.clone();
+            if self.is_blocked(&cube, level - 1, true) {
+                let cube = self.generalize(cube, level - 1);
+                // push the cube as far as it stays blocked
+                let mut at = level;
+                while at < self.levels.len() && self.is_blocked(&cube, at, true) {
+                    at += 1;
+                }
+                self.block_at(cube, at);
+            } else {
+                let predecessor = Obligation {
+                    state: self.unrolling.state(0),
+                    cube: self.cube(0),
+                    parent: Some((id, self.unrolling.label(0))),
+                };
+                self.obligations.push(predecessor);
+                let pred_id = self.obligations.len() - 1;
+                if level == 1 {
+                    return Some(pred_id);
+                }
+                queue.push(Reverse((level - 1, pred_id)));
+                queue.push(Reverse((level, id)));
+            }
+        }
+        None
+    }
+
+    /// the path of the states of the obligations from `id`.
+    fn path(&self, id: usize) -> Path {
+        let mut states = vec![self.obligations[id].state.clone()];
+        let mut steps = vec![];
+        let mut id = id;
+        while let Some((parent, label)) = &self.obligations[id].parent {
+            states.push(self.obligations[*parent].state.clone());
+            steps.push((label.clone(), states.len() - 1));
+            id = *parent;
+        }
+        Path {
+            states,
+            trace: Trace { start: 0, steps },
+        }
+    }
+}
+
+/// Proves `AG invariant` for the state formula `invariant` with IC3/PDR, finding an
+/// inductive invariant without unrolling the model.
+pub fn pdr(
+    model: &Model,
+    prop_expr: fn(&Prop) -> Option<Expr>,
+    invariant: &Formula,
+) -> Result<Verdict, String> {
+    let mut unrolling = Unrolling::new(model)?;
+    unrolling.add_state();
+    let step = unrolling.add_optional_step();
+    let init = unrolling.init(0);
+    let good = unrolling.formula(prop_expr, invariant, 0)?;
+    let init_bits = unrolling.init_bits();
+    let mut pdr = Pdr {
+        unrolling,
+        step,
+        init,
+        good,
+        init_bits,
+        levels: vec![],
+        frames: vec![],
+        obligations: vec![],
+    };
+    if pdr.unrolling.cnf.solver.solve_with(&[init, !good]) {
+        let path = pdr.unrolling.path(1);
+        return Ok(Verdict::Violated(path));
+    }
+    let level = pdr.unrolling.cnf.fresh();
+    pdr.levels.push(level);
+    pdr.frames.push(vec![]);
+    loop {
+        let top = pdr.levels.len();
+        let mut assumptions = pdr.levels[top - 1..].to_vec();
+        assumptions.push(!pdr.good);
+        while pdr.unrolling.cnf.solver.solve_with(&assumptions) {
+            pdr.obligations.push(Obligation {
+                state: pdr.unrolling.state(0),
+                cube: pdr.cube(0),
+                parent: None,
+            });
+            let id = pdr.obligations.len() - 1;
+            if let Some(start) = pdr.block(id, top) {
+                return Ok(Verdict::Violated(pdr.path(start)));
+            }
+        }
+        let level = pdr.unrolling.cnf.fresh();
+        pdr.levels.push(level);
+        pdr.frames.push(vec![]);
+        // propagate the blocked cubes forward, until two frames are equal
+        for level in 1..=top {
+            for cube in pdr.frames[level - 1].clone() {
+                if pdr.is_blocked(&cube, level, false) {
+                    pdr.frames[level - 1].retain(|c| c != &cube);
+                    pdr.block_at(cube, level + 1);
+                }
+            }
+            if pdr.frames[level - 1].is_empty() {
+                let mut clauses: Vec<Vec<BitLit>> = vec![];
+                for cube in pdr.frames[level..].iter().flatten() {
+                    let clause = cube
+                        .iter()
+                        .map(|bit| BitLit {
+                            value: !bit.value,
+                            ..*bit
+                        })
+                        .collect();
+                    if !clauses.contains(&clause) {
+                        clauses.push(clause);
+                    }
+                }
+                return Ok(Verdict::Proved(Certificate::Clauses(clauses)));
+            }
+        }
+    }
+}
+
+use std::fmt;
+impl fmt::Display for BitLit {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.value {
+            write!(fmt, "s{}.{}", self.slot, self.bit)
+        } else {
+            write!(fmt, "(not s{}.{})", self.slot, self.bit)
+        }
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Certificate::KInductive(k) => write!(fmt, "{}-inductive", k),
+            Certificate::Clauses(clauses) => {
+                write!(fmt, "(and")?;
+                for clause in clauses.iter() {
+                    let bits: Vec<String> = clause.iter().map(|bit| bit.to_string()).collect();
+                    write!(fmt, " (or {})", bits.join(" "))?;
+                }
+                write!(fmt, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bmc,
+        symbolic::tests::{below, counter, prop_expr},
+    };
+
+    fn last_x(path: &Path) -> u64 {
+        path.states.last().unwrap().vars[0]
+    }
+
+    #[test]
+    fn violations_at_a_deadlock_are_found() {
+        let model = counter(3, 3);
+        let path = bmc::check_invariant(&model, prop_expr, &below(3), 5).unwrap();
+        assert_eq!(last_x(&path.unwrap()), 3);
+        match k_induction(&model, prop_expr, &below(3), 5).unwrap() {
+            Verdict::Violated(path) => {
+                assert_eq!(path.trace.steps.len(), 3);
+                assert_eq!(last_x(&path), 3);
+            }
+            verdict => panic!("k-induction: {:?}", verdict),
+        }
+        match pdr(&model, prop_expr, &below(3)).unwrap() {
+            Verdict::Violated(path) => {
+                assert_eq!(path.states[0].vars[0], 0);
+                assert_eq!(last_x(&path), 3);
+            }
+            verdict => panic!("pdr: {:?}", verdict),
+        }
+    }
+
+    #[test]
+    fn invariants_up_to_a_deadlock_are_proved() {
+        let model = counter(3, 3);
+        assert!(bmc::check_invariant(&model, prop_expr, &below(4), 5)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            k_induction(&model, prop_expr, &below(4), 5).unwrap(),
+            Verdict::Proved(Certificate::KInductive(_))
+        ));
+        assert!(matches!(
+            pdr(&model, prop_expr, &below(4)).unwrap(),
+            Verdict::Proved(Certificate::Clauses(_))
+        ));
+    }
+}
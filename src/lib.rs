@@ -2,8 +2,29 @@
 
 extern crate bimap;
 
+pub mod abstraction;
+pub mod atl;
+pub mod bdd;
+pub mod bisim;
+pub mod bmc;
+pub mod cegar;
+pub mod ctlstar;
+pub mod dtmc;
+pub mod equiv;
 pub mod formula;
+mod graph;
+pub mod induction;
+pub mod ltl;
 pub mod lts;
 pub mod mark;
+pub mod mdp;
+pub mod mu;
+pub mod pctl;
 pub mod process;
+pub mod query;
+pub mod refine;
+pub mod reward;
+pub mod sat;
+pub mod symbolic;
+pub mod timed;
 pub mod viz;
@@ -0,0 +1,557 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use crate::{
+    formula::{Formula, Prop},
+    graph,
+    lts::{Lts, StateId, Trace, STUTTER},
+    mark::{self, Deadlock, Options},
+    process::Label,
+};
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Ltl<A = Formula> {
+    True,
+    False,
+    /// a state formula, evaluated at each position of the path.
+    Atom(A),
+    Not(Box<Ltl<A>>),
+    And(Box<Ltl<A>>, Box<Ltl<A>>),
+    Or(Box<Ltl<A>>, Box<Ltl<A>>),
+    Impl(Box<Ltl<A>>, Box<Ltl<A>>),
+    X(Box<Ltl<A>>),
+    U(Box<Ltl<A>>, Box<Ltl<A>>),
+    /// release, the dual of `U`.
+    R(Box<Ltl<A>>, Box<Ltl<A>>),
+    F(Box<Ltl<A>>),
+    G(Box<Ltl<A>>),
+}
+
+/// A lasso-shaped path: the stem leads from the initial state to a cycle which
+/// returns to the last state of the stem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lasso {
+    pub stem: Trace,
+    pub cycle: Vec<(Label, StateId)>,
+}
+
+/// Checks that every path from the initial state 0 satisfies `spec`,
+/// returning a violating path if there is one.
+///
+/// under `Deadlock::Finite` a path reaching a terminal state stutters there forever;
+/// under `Deadlock::Fixpoint` only the infinite paths count.
+pub fn check<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    spec: Ltl,
+    deadlock: Deadlock,
+) -> Result<Option<Lasso>, String> {
+    let mut looped = mark::apply_deadlock(lts, deadlock)?;
+    let lts = looped.as_mut().unwrap_or(lts);
+
+    let (atoms, gba) = translate(&Ltl::Not(Box::new(spec)));
+    let options = Options {
+        deadlock,
+        ..Default::default()
+    };
+    let mut truth = vec![];
+    for atom in atoms {
+        mark::reject_epistemic(&atom)?;
+        let marks = mark::make_marks_with(lts, prop_valuate, atom, &options)?;
+        truth.push((0..lts.0.len()).map(|id| marks.holds_at(id)).collect());
+    }
+
+    let product = Product::new(lts, &gba, &truth, &[0], deadlock);
+    Ok(product.accepting_lasso(&gba).map(|(stem, cycle)| Lasso {
+        stem: product.trace(lts, &stem),
+        cycle: product.trace(lts, &cycle).steps,
+    }))
+}
+
+/// ltl in negation normal form, with atoms numbered.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+enum Nnf {
+    True,
+    False,
+    Lit(usize, bool),
+    And(Box<Nnf>, Box<Nnf>),
+    Or(Box<Nnf>, Box<Nnf>),
+    X(Box<Nnf>),
+    U(Box<Nnf>, Box<Nnf>),
+    R(Box<Nnf>, Box<Nnf>),
+}
+
+fn nnf<A: Clone + Eq>(f: &Ltl<A>, positive: bool, atoms: &mut Vec<A>) -> Nnf {
+    use Ltl::*;
+    let b = Box::new;
+    match f {
+        True if positive => Nnf::True,
+        True => Nnf::False,
+        False if positive => Nnf::False,
+        False => Nnf::True,
+        Atom(a) => {
+            let i = match atoms.iter().position(|x| x == a) {
+                Some(i) => i,
+                None => {
+                    atoms.push(a.clone());
+                    atoms.len() - 1
+                }
+            };
+            Nnf::Lit(i, positive)
+        }
+        Not(f) => nnf(f, !positive, atoms),
+        And(f, g) if positive => Nnf::And(b(nnf(f, true, atoms)), b(nnf(g, true, atoms))),
+        And(f, g) => Nnf::Or(b(nnf(f, false, atoms)), b(nnf(g, false, atoms))),
+        Or(f, g) if positive => Nnf::Or(b(nnf(f, true, atoms)), b(nnf(g, true, atoms))),
+        Or(f, g) => Nnf::And(b(nnf(f, false, atoms)), b(nnf(g, false, atoms))),
+        Impl(f, g) if positive => Nnf::Or(b(nnf(f, false, atoms)), b(nnf(g, true, atoms))),
+        Impl(f, g) => Nnf::And(b(nnf(f, true, atoms)), b(nnf(g, false, atoms))),
+        X(f) => Nnf::X(b(nnf(f, positive, atoms))),
+        U(f, g) if positive => Nnf::U(b(nnf(f, true, atoms)), b(nnf(g, true, atoms))),
+        U(f, g) => Nnf::R(b(nnf(f, false, atoms)), b(nnf(g, false, atoms))),
+        R(f, g) if positive => Nnf::R(b(nnf(f, true, atoms)), b(nnf(g, true, atoms))),
+        R(f, g) => Nnf::U(b(nnf(f, false, atoms)), b(nnf(g, false, atoms))),
+        F(f) if positive => Nnf::U(b(Nnf::True), b(nnf(f, true, atoms))),
+        F(f) => Nnf::R(b(Nnf::False), b(nnf(f, false, atoms))),
+        G(f) if positive => Nnf::R(b(Nnf::False), b(nnf(f, true, atoms))),
+        G(f) => Nnf::U(b(Nnf::True), b(nnf(f, false, atoms))),
+    }
+}
+
+fn untils(f: &Nnf, acc: &mut Vec<Nnf>) {
+    match f {
+        Nnf::True | Nnf::False | Nnf::Lit(_, _) => {}
+        Nnf::X(g) => untils(g, acc),
+        Nnf::And(g, h) | Nnf::Or(g, h) | Nnf::R(g, h) => {
+            untils(g, acc);
+            untils(h, acc);
+        }
+        Nnf::U(g, h) => {
+            untils(g, acc);
+            untils(h, acc);
+            if !acc.contains(f) {
+                acc.push(f.clone());
+            }
+        }
+    }
+}
+
+/// A generalized Büchi automaton whose states are labelled with the literals
+/// the current lts state has to satisfy.
+pub(crate) struct Gba {
+    labels: Vec<Vec<(usize, bool)>>,
+    init: Vec<usize>,
+    succs: Vec<Vec<usize>>,
+    /// for each acceptance set, whether each automaton state belongs to it.
+    accepting: Vec<Vec<bool>>,
+}
+
+/// translates the formula into a Büchi automaton accepting exactly the paths
+/// satisfying it (Gerth, Peled, Vardi and Wolper's tableau construction).
+/// also returns the atoms, numbered as in the automaton labels.
+pub(crate) fn translate<A: Clone + Eq>(f: &Ltl<A>) -> (Vec<A>, Gba) {
+    #[derive(Clone)]
+    struct Node {
+        incoming: HashSet<usize>,
+        new: Vec<Nnf>,
+        old: HashSet<Nnf>,
+        next: HashSet<Nnf>,
+    }
+    const INIT: usize = usize::MAX;
+
+    let mut atoms = vec![];
+    let f = nnf(f, true, &mut atoms);
+
+    let mut nodes: Vec<Node> = vec![];
+    let mut pending = vec![Node {
+        incoming: [INIT].iter().cloned().collect(),
+        new: vec![f.clone()],
+        old: HashSet::new(),
+        next: HashSet::new(),
+    }];
+    while let Some(mut node) = pending.pop() {
+        let g = match node.new.pop() {
+            Some(g) => g,
+            None => {
+                // fully expanded
+                match nodes
+                    .iter_mut()
+                    .find(|nd| nd.old == node.old && nd.next == node.next)
+                {
+                    Some(nd) => nd.incoming.extend(node.incoming),
+                    None => {
+                        pending.push(Node {
+                            incoming: [nodes.len()].iter().cloned().collect(),
+                            new: node.next.iter().cloned().collect(),
+                            old: HashSet::new(),
+                            next: HashSet::new(),
+                        });
+                        nodes.push(node);
+                    }
+                }
+                continue;
+            }
+        };
+        if node.old.contains(&g) {
+            pending.push(node);
+            continue;
+        }
+        node.old.insert(g.clone());
+        match g {
+            Nnf::True => pending.push(node),
+            Nnf::False => {} // contradiction
+            Nnf::Lit(i, b) => {
+                if !node.old.contains(&Nnf::Lit(i, !b)) {
+                    pending.push(node)
+                }
+            }
+            Nnf::And(h1, h2) => {
+                node.new.push(*h1);
+                node.new.push(*h2);
+                pending.push(node);
+            }
+            Nnf::Or(h1, h2) => {
+                let mut node2 = node.clone();
+                node.new.push(*h1);
+                node2.new.push(*h2);
+                pending.push(node);
+                pending.push(node2);
+            }
+            Nnf::X(h) => {
+                node.next.insert(*h);
+                pending.push(node);
+            }
+            Nnf::U(ref h1, ref h2) => {
+                let mut node2 = node.clone();
+                node.new.push(*h1.clone());
+                node.next.insert(g.clone());
+                node2.new.push(*h2.clone());
+                pending.push(node);
+                pending.push(node2);
+            }
+            Nnf::R(ref h1, ref h2) => {
+                let mut node2 = node.clone();
+                node.new.push(*h2.clone());
+                node.next.insert(g.clone());
+                node2.new.push(*h1.clone());
+                node2.new.push(*h2.clone());
+                pending.push(node);
+                pending.push(node2);
+            }
+        }
+    }
+
+    let mut succs = vec![vec![]; nodes.len()];
+    let mut init = vec![];
+    for (q, node) in nodes.iter().enumerate() {
+        for p in node.incoming.iter() {
+            if *p == INIT {
+                init.push(q);
+            } else {
+                succs[*p].push(q);
+            }
+        }
+    }
+    let mut us = vec![];
+    untils(&f, &mut us);
+    let accepting = us
+        .iter()
+        .map(|u| match u {
+            Nnf::U(_, h) => nodes
+                .iter()
+                .map(|node| !node.old.contains(u) || node.old.contains(h))
+                .collect(),
+            _ => unreachable!(),
+        })
+        .collect();
+    let labels = nodes
+        .iter()
+        .map(|node| {
+            node.old
+                .iter()
+                .filter_map(|g| match g {
+                    Nnf::Lit(i, b) => Some((*i, *b)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let gba = Gba {
+        labels,
+        init,
+        succs,
+        accepting,
+    };
+    (atoms, gba)
+}
+
+/// The synchronous product of an lts and a Büchi automaton, explored from the given
+/// lts states. terminal lts states stutter under `Deadlock::Finite`, and end the paths
+/// otherwise.
+pub(crate) struct Product {
+    /// the lts state and the automaton state of each product state.
+    pub(crate) states: Vec<(StateId, usize)>,
+    /// successors, with the index of the lts transition taken (`None` when stuttering).
+    succs: Vec<Vec<(usize, Option<usize>)>>,
+    pub(crate) roots: Vec<usize>,
+}
+
+impl Product {
+    /// `truth[atom][state_id]` tells whether the atom holds at the lts state.
+    pub(crate) fn new<T>(
+        lts: &Lts<T>,
+        gba: &Gba,
+        truth: &[Vec<bool>],
+        starts: &[StateId],
+        deadlock: Deadlock,
+    ) -> Product {
+        let matches = |state_id: StateId, q: usize| {
+            gba.labels[q]
+                .iter()
+                .all(|(atom, b)| truth[*atom][state_id] == *b)
+        };
+        let mut product = Product {
+            states: vec![],
+            succs: vec![],
+            roots: vec![],
+        };
+        let mut dict = HashMap::new();
+        let mut queue = VecDeque::new();
+        fn add(
+            product: &mut Product,
+            dict: &mut HashMap<(StateId, usize), usize>,
+            queue: &mut VecDeque<usize>,
+            s: (StateId, usize),
+        ) -> usize {
+            *dict.entry(s).or_insert_with(|| {
+                product.states.push(s);
+                product.succs.push(vec![]);
+                queue.push_back(product.states.len() - 1);
+                product.states.len() - 1
+            })
+        }
+        for state_id in starts {
+            for q in gba.init.iter() {
+                if matches(*state_id, *q) {
+                    let root = add(&mut product, &mut dict, &mut queue, (*state_id, *q));
+                    product.roots.push(root);
+                }
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            let (state_id, q) = product.states[id];
+            let trans = &lts.0[state_id];
+            let nexts: Vec<(StateId, Option<usize>)> =
+                if trans.steps.is_empty() && deadlock == Deadlock::Finite {
+                    vec![(state_id, None)]
+                } else {
+                    trans
+                        .steps
+                        .iter()
+                        .enumerate()
+                        .map(|(k, step)| (step.dst, Some(k)))
+                        .collect()
+                };
+            for (next_id, k) in nexts {
+                for next_q in gba.succs[q].iter() {
+                    if matches(next_id, *next_q) {
+                        let succ = add(&mut product, &mut dict, &mut queue, (next_id, *next_q));
+                        product.succs[id].push((succ, k));
+                    }
+                }
+            }
+        }
+        product
+    }
+
+    fn succ_ids(&self, id: usize) -> Vec<usize> {
+        self.succs[id].iter().map(|(succ, _)| *succ).collect()
+    }
+
+    /// strongly connected components on which a path can visit every acceptance set
+    /// infinitely often.
+    fn accepting_sccs(&self, gba: &Gba) -> Vec<Vec<usize>> {
+        graph::sccs(self.states.len(), &self.roots, |v| self.succ_ids(v))
+            .into_iter()
+            .filter(|component| {
+                graph::is_nontrivial(component, |v| self.succ_ids(v))
+                    && gba
+                        .accepting
+                        .iter()
+                        .all(|set| component.iter().any(|id| set[self.states[*id].1]))
+            })
+            .collect()
+    }
+
+    /// product states from which an accepting path starts.
+    pub(crate) fn accepting_states(&self, gba: &Gba) -> Vec<bool> {
+        let mut preds = vec![vec![]; self.states.len()];
+        for (id, succs) in self.succs.iter().enumerate() {
+            for (succ, _) in succs {
+                preds[*succ].push(id);
+            }
+        }
+        let targets: Vec<usize> = self.accepting_sccs(gba).into_iter().flatten().collect();
+        graph::backward_reach(self.states.len(), &targets, |v| preds[v].clone(), |_| true)
+    }
+
+    /// an accepting path from a root, as a stem and a cycle of product states.
+    /// the cycle starts and ends at the last state of the stem.
+    fn accepting_lasso(&self, gba: &Gba) -> Option<(Vec<usize>, Vec<usize>)> {
+        let mut component_of = vec![None; self.states.len()];
+        for (c, component) in self.accepting_sccs(gba).iter().enumerate() {
+            for id in component {
+                component_of[*id] = Some(c);
+            }
+        }
+
+        let stem = self.shortest_path(&self.roots, |_| true, |id| component_of[id].is_some())?;
+        let entry = *stem.last().unwrap();
+        let inside = |id: usize| component_of[id] == component_of[entry];
+
+        let mut cycle = vec![entry];
+        for set in gba.accepting.iter() {
+            let current = *cycle.last().unwrap();
+            let path = self.shortest_path(&[current], inside, |id| set[self.states[id].1])?;
+            cycle.extend(path.into_iter().skip(1));
+        }
+        // close the cycle with at least one step
+        let current = *cycle.last().unwrap();
+        let back = self.shortest_path(&self.succ_ids(current), inside, |id| id == entry)?;
+        cycle.extend(back);
+        Some((stem, cycle))
+    }
+
+    /// a shortest path from one of `starts` to a `goal` state, staying `inside`.
+    fn shortest_path(
+        &self,
+        starts: &[usize],
+        inside: impl Fn(usize) -> bool,
+        goal: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let mut parent = HashMap::new();
+        let mut queue = VecDeque::new();
+        for start in starts {
+            if inside(*start) && !parent.contains_key(start) {
+                parent.insert(*start, None);
+                queue.push_back(*start);
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            if goal(id) {
+                let mut path = vec![id];
+                while let Some(Some(prev)) = parent.get(path.last().unwrap()) {
+                    path.push(*prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for succ in self.succ_ids(id) {
+                if inside(succ) && !parent.contains_key(&succ) {
+                    parent.insert(succ, Some(id));
+                    queue.push_back(succ);
+                }
+            }
+        }
+        None
+    }
+
+    /// the lts path followed by a path of product states.
+    pub(crate) fn trace<T>(&self, lts: &Lts<T>, path: &[usize]) -> Trace {
+        let steps = path
+            .windows(2)
+            .map(|w| {
+                let (state_id, _) = self.states[w[0]];
+                let (next_id, _) = self.states[w[1]];
+                let k = self.succs[w[0]]
+                    .iter()
+                    .find(|(succ, _)| *succ == w[1])
+                    .unwrap()
+                    .1;
+                let label = match k {
+                    Some(k) => lts.0[state_id].steps[k].label.clone(),
+                    None => Label::new(STUTTER),
+                };
+                (label, next_id)
+            })
+            .collect();
+        Trace {
+            start: self.states[path[0]].0,
+            steps,
+        }
+    }
+}
+
+use std::fmt;
+impl<A: fmt::Display> fmt::Display for Ltl<A> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Ltl::*;
+        match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
+            Atom(ref a) => write!(fmt, "{}", a),
+            Not(ref f) => write!(fmt, "(not {})", f),
+            And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
+            Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
+            Impl(ref lhs, ref rhs) => write!(fmt, "(impl {} {})", lhs, rhs),
+            X(ref f) => write!(fmt, "(X {})", f),
+            U(ref f, ref g) => write!(fmt, "(U {} {})", f, g),
+            R(ref f, ref g) => write!(fmt, "(R {} {})", f, g),
+            F(ref f) => write!(fmt, "(F {})", f),
+            G(ref f) => write!(fmt, "(G {})", f),
+        }
+    }
+}
+
+impl fmt::Display for Lasso {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} (", self.stem)?;
+        for (label, state_id) in self.cycle.iter() {
+            write!(fmt, " -{}-> {}", label, state_id)?;
+        }
+        write!(fmt, " )")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::tests::lts, process::Location};
+
+    #[test]
+    fn terminal_states_end_paths_unless_finite() {
+        // `0 -a-> 1`, where 1 is terminal: `G false` only fails on the stuttering path
+        let spec = || Ltl::G(Box::new(Ltl::False));
+        let mut fixpoint = lts(vec![vec![("a", 1, Some(0))], vec![]]);
+        assert_eq!(
+            check(&mut fixpoint, |_, _| false, spec(), Deadlock::Fixpoint),
+            Ok(None)
+        );
+        let mut finite = lts(vec![vec![("a", 1, Some(0))], vec![]]);
+        let lasso = check(&mut finite, |_, _| false, spec(), Deadlock::Finite)
+            .unwrap()
+            .unwrap();
+        assert_eq!(lasso.stem.last(), 1);
+    }
+
+    #[test]
+    fn response_fails_on_a_lasso() {
+        // `G F at B`: process 0 keeps coming back to `B`, unless it moves to 2 and stays at `A`
+        let at_b = || Ltl::Atom(Formula::At(0, Location::new("B")));
+        let spec = || Ltl::G(Box::new(Ltl::F(Box::new(at_b()))));
+        let mut cycle = lts(vec![vec![("a", 1, Some(0))], vec![("c", 0, Some(0))]]);
+        assert_eq!(
+            check(&mut cycle, |_, _| false, spec(), Deadlock::Fixpoint),
+            Ok(None)
+        );
+        let mut escape = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![("c", 0, Some(0))],
+            vec![("d", 2, Some(0))],
+        ]);
+        let lasso = check(&mut escape, |_, _| false, spec(), Deadlock::Fixpoint)
+            .unwrap()
+            .unwrap();
+        assert_eq!(lasso.to_string(), "0 -b-> 2 ( -d-> 2 )");
+    }
+}
@@ -1,25 +1,84 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::Hash;
 
-use crate::process::{self, ExecUnit, Label, Location, Process};
+use crate::{
+    process::{self, ExecUnit, Label, Location, Process},
+    timed::Dbm,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct State<T> {
     pub vars: T,
     pub locations: Vec<Location>,
+    /// the clock valuations of a state of `timed::zone_graph`, and `None` in the untimed
+    /// compositions.
+    pub zone: Option<Dbm>,
 }
 
 pub type StateId = usize;
 
-#[derive(Debug)]
+/// A transition out of a state of an `Lts`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub label: Label,
+    pub dst: StateId,
+    /// index of the process which fired the transition, or `None` for a stuttering
+    /// self-loop.
+    pub process: Option<usize>,
+    /// the weight of the label given to `Lts::weighted_composition`, and `1.0` otherwise.
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone)]
 pub struct Trans<T> {
     pub state: State<T>,
-    pub dst: Vec<(Label, StateId)>,
+    pub steps: Vec<Step>,
+}
+
+/// A finite path through an `Lts`: the state it starts from and the labelled steps taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub start: StateId,
+    pub steps: Vec<(Label, StateId)>,
+}
+
+impl Trace {
+    pub fn last(&self) -> StateId {
+        self.steps
+            .last()
+            .map_or(self.start, |(_, state_id)| *state_id)
+    }
 }
 
-#[derive(Debug)]
+/// label of the self-loops added by `Lts::add_stutter_loops`, which are told apart from
+/// the transitions of the processes by their lack of `Step::process`.
+pub const STUTTER: &str = "stutter";
+
+/// label of the internal transitions, as renamed by `Lts::hide`. the processes may not use it.
+pub const TAU: &str = "tau";
+
+impl<T> Trans<T> {
+    /// a state is terminal if it has no successor other than stuttering on itself: no
+    /// process can move, whatever the labels.
+    pub fn is_terminal(&self) -> bool {
+        self.steps.iter().all(|step| step.process.is_none())
+    }
+
+    /// whether the process has an outgoing transition from this state.
+    pub fn is_enabled(&self, process: usize) -> bool {
+        self.steps.iter().any(|step| step.process == Some(process))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Lts<T>(pub Vec<Trans<T>>);
 
+impl<T: Clone + Hash + Eq> Default for Lts<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Clone + Hash + Eq> Lts<T> {
     pub fn new() -> Self {
         Lts(vec![])
@@ -35,43 +94,211 @@ impl<T: Clone + Hash + Eq> Lts<T> {
         result
     }
 
+    /// the source states of the transitions entering each state.
+    pub fn predecessors(&self) -> Vec<Vec<StateId>> {
+        let mut preds = vec![vec![]; self.0.len()];
+        for (state_id, trans) in self.0.iter().enumerate() {
+            for step in trans.steps.iter() {
+                if !preds[step.dst].contains(&state_id) {
+                    preds[step.dst].push(state_id);
+                }
+            }
+        }
+        preds
+    }
+
+    pub fn terminal_states(&self) -> Vec<StateId> {
+        self.find_states(|_, trans| trans.is_terminal())
+    }
+
+    /// make the transition relation total by letting every terminal state loop on itself.
+    pub fn add_stutter_loops(&mut self) {
+        for (state_id, trans) in self.0.iter_mut().enumerate() {
+            if trans.steps.is_empty() {
+                trans.steps.push(Step {
+                    label: Label::new(STUTTER),
+                    dst: state_id,
+                    process: None,
+                    weight: 1.0,
+                });
+            }
+        }
+    }
+
+    /// rename the transitions with one of the labels to the internal `TAU`.
+    pub fn hide(&mut self, labels: &[Label]) {
+        for trans in self.0.iter_mut() {
+            for step in trans.steps.iter_mut() {
+                if labels.contains(&step.label) {
+                    step.label = Label::new(TAU);
+                }
+            }
+        }
+    }
+
     // TODO: don't use String as error type!
     pub fn concurrent_composition(vars: T, processes: Vec<Process<T>>) -> Result<Lts<T>, String> {
-        let s0 = State {
-            vars: vars,
-            locations: {
-                let locations: Result<Vec<Location>, _> =
-                    processes.iter().map(pick_init_location).collect();
-                locations?
-            },
+        Self::weighted_composition(vars, processes, |_| 1.0)
+    }
+
+    /// `concurrent_composition` where each transition gets the weight of its label: its
+    /// relative likelihood among the transitions its process can take from the same state,
+    /// which only the probabilistic models read.
+    pub fn weighted_composition(
+        vars: T,
+        processes: Vec<Process<T>>,
+        weight: fn(&Label) -> f64,
+    ) -> Result<Lts<T>, String> {
+        processes.iter().flatten().try_for_each(check_untimed)?;
+        let s0 = init_state(vars, &processes)?;
+        Ok(bfs(s0, |state| {
+            successors(&processes, state)
+                .into_iter()
+                .map(|(label, process_id, next)| {
+                    let w = weight(&label);
+                    (label, process_id, w, next)
+                })
+                .collect()
+        }))
+    }
+
+    /// The existential abstraction of `concurrent_composition`: the reachable states with
+    /// the same locations and abstract value of their variables are merged into one state,
+    /// which has the transitions of all of them.
+    ///
+    /// a merged state which is terminal gets a stuttering self-loop, so that every path of
+    /// the stuttering system has an abstract counterpart.
+    pub fn abstract_composition<C: Clone + Hash + Eq>(
+        vars: C,
+        processes: &[Process<C>],
+        abstraction: impl Fn(&C) -> T,
+    ) -> Result<Lts<T>, String> {
+        processes.iter().flatten().try_for_each(check_untimed)?;
+        let s0 = init_state(vars, processes)?;
+        let abstract_state = |state: &State<C>| State {
+            vars: abstraction(&state.vars),
+            locations: state.locations.clone(),
+            zone: None,
         };
-        let next_func = |state: &State<T>| -> Vec<(Label, State<T>)> {
-            let mut next = vec![];
-            for (i, process) in processes.iter().enumerate() {
-                let location = state.locations[i].clone();
-                let transs = &process
-                    .iter()
-                    .find(|exec: &&ExecUnit<T>| exec.src == location)
-                    .unwrap()
-                    .transs;
-                calc_transitions_from(&mut next, &location, state, transs);
-            }
-            return next;
+        let mut lts = Lts::new();
+        let mut ids = HashMap::new();
+        let mut add = |lts: &mut Lts<T>, state: State<T>| -> StateId {
+            *ids.entry(state.clone()).or_insert_with(|| {
+                lts.0.push(Trans {
+                    state,
+                    steps: vec![],
+                });
+                lts.0.len() - 1
+            })
         };
-        Ok(bfs(s0, next_func))
+        add(&mut lts, abstract_state(&s0));
+        let mut visited = HashSet::new();
+        visited.insert(s0.clone());
+        let mut queue = VecDeque::new();
+        queue.push_back(s0);
+        while let Some(state) = queue.pop_front() {
+            let state_id = add(&mut lts, abstract_state(&state));
+            let nexts = successors(processes, &state);
+            let mut steps = vec![];
+            if nexts.is_empty() {
+                steps.push(Step {
+                    label: Label::new(STUTTER),
+                    dst: state_id,
+                    process: None,
+                    weight: 1.0,
+                });
+            }
+            for (label, process_id, next_state) in nexts {
+                steps.push(Step {
+                    label,
+                    dst: add(&mut lts, abstract_state(&next_state)),
+                    process: Some(process_id),
+                    weight: 1.0,
+                });
+                if visited.insert(next_state.clone()) {
+                    queue.push_back(next_state);
+                }
+            }
+            let trans = &mut lts.0[state_id];
+            for step in steps {
+                let known = trans.steps.iter().any(|s| {
+                    s.label == step.label && s.dst == step.dst && s.process == step.process
+                });
+                if !known {
+                    trans.steps.push(step);
+                }
+            }
+        }
+        Ok(lts)
     }
 }
 
+/// the transitions of the processes from the state, with the index of the process taking
+/// each of them.
+pub(crate) fn successors<T: Clone>(
+    processes: &[Process<T>],
+    state: &State<T>,
+) -> Vec<(Label, usize, State<T>)> {
+    let mut next = vec![];
+    for (i, process) in processes.iter().enumerate() {
+        let location = state.locations[i].clone();
+        let transs = &process
+            .iter()
+            .find(|exec: &&ExecUnit<T>| exec.src == location)
+            .unwrap()
+            .transs;
+        calc_transitions_from(&mut next, i, state, transs);
+    }
+    next
+}
+
+/// fails on the label `TAU`, which is reserved for the transitions hidden by `Lts::hide`.
+pub(crate) fn check_label(label: &Label) -> Result<(), String> {
+    if label.0 == TAU {
+        Err(format!("label {} is reserved for hidden transitions", TAU))
+    } else {
+        Ok(())
+    }
+}
+
+/// fails on a reserved label or on clocks, which only `timed::zone_graph` handles.
+fn check_untimed<T>(exec: &ExecUnit<T>) -> Result<(), String> {
+    let timed = !exec.invariant.is_empty()
+        || exec
+            .transs
+            .iter()
+            .any(|trans| !trans.clock_guard.is_empty() || !trans.resets.is_empty());
+    if timed {
+        return Err(format!(
+            "location {} has clocks, which need a zone graph",
+            exec.src
+        ));
+    }
+    exec.transs
+        .iter()
+        .try_for_each(|trans| check_label(&trans.label))
+}
+
+/// the state where each process is at its first location.
+pub(crate) fn init_state<T>(vars: T, processes: &[Process<T>]) -> Result<State<T>, String> {
+    let locations: Result<Vec<Location>, _> = processes.iter().map(pick_init_location).collect();
+    Ok(State {
+        vars,
+        locations: locations?,
+        zone: None,
+    })
+}
+
 fn pick_init_location<T>(p: &Process<T>) -> Result<Location, String> {
     match p.first() {
         Some(exec) => Ok(exec.src.clone()),
-        None => Err(format!("wrong process: no transition")),
+        None => Err("wrong process: no transition".to_string()),
     }
 }
 
 fn calc_transitions_from<T: Clone>(
-    next: &mut Vec<(Label, State<T>)>,
-    current: &Location,
+    next: &mut Vec<(Label, usize, State<T>)>,
+    process_id: usize,
     state: &State<T>,
     transs: &Vec<process::Trans<T>>,
 ) {
@@ -80,8 +307,9 @@ fn calc_transitions_from<T: Clone>(
             let locations = state
                 .locations
                 .iter()
-                .map(|l: &Location| {
-                    if l == current {
+                .enumerate()
+                .map(|(i, l): (usize, &Location)| {
+                    if i == process_id {
                         trans.dst.clone()
                     } else {
                         l.clone()
@@ -90,16 +318,17 @@ fn calc_transitions_from<T: Clone>(
                 .collect();
             let dst_state = State {
                 vars: (trans.action)(state.vars.clone()),
-                locations: locations,
+                locations,
+                zone: None,
             };
-            next.push((trans.label.clone(), dst_state));
+            next.push((trans.label.clone(), process_id, dst_state));
         }
     }
 }
 
-fn bfs<T: Clone + Hash + Eq>(
+pub(crate) fn bfs<T: Clone + Hash + Eq>(
     init: State<T>,
-    next_func: impl Fn(&State<T>) -> Vec<(Label, State<T>)>,
+    next_func: impl Fn(&State<T>) -> Vec<(Label, usize, f64, State<T>)>,
 ) -> Lts<T> {
     let mut lts = Lts::new();
     let mut state_dict = HashMap::new();
@@ -107,31 +336,166 @@ fn bfs<T: Clone + Hash + Eq>(
     let mut queue = VecDeque::new();
     queue.push_back((0, init));
 
-    loop {
-        if let Some((state_id, state)) = queue.pop_front() {
-            let nexts = next_func(&state);
-            let mut dst = Vec::with_capacity(nexts.len());
-            for (label, next_state) in nexts {
-                let next_id = if let Some(id) = state_dict.get(&next_state) {
-                    *id // already exists
-                } else {
-                    let id = state_dict.len();
-                    state_dict.insert(next_state.clone(), id);
-                    queue.push_back((id, next_state.clone()));
-                    id
-                };
-                dst.push((label, next_id));
-            }
-            lts.0.insert(
-                state_id,
-                Trans {
-                    state: state,
-                    dst: dst,
-                },
-            );
-        } else {
-            break;
+    while let Some((state_id, state)) = queue.pop_front() {
+        let nexts = next_func(&state);
+        let mut steps = Vec::with_capacity(nexts.len());
+        for (label, process_id, weight, next_state) in nexts {
+            let next_id = if let Some(id) = state_dict.get(&next_state) {
+                *id // already exists
+            } else {
+                let id = state_dict.len();
+                state_dict.insert(next_state.clone(), id);
+                queue.push_back((id, next_state.clone()));
+                id
+            };
+            steps.push(Step {
+                label,
+                dst: next_id,
+                process: Some(process_id),
+                weight,
+            });
         }
+        lts.0.insert(state_id, Trans { state, steps });
     }
     lts
 }
+
+/// a shortest path from the initial state.
+pub(crate) fn shortest_trace<T>(lts: &Lts<T>, target: StateId) -> Trace {
+    let mut parent: Vec<Option<(StateId, Label)>> = vec![None; lts.0.len()];
+    let mut visited = vec![false; lts.0.len()];
+    visited[0] = true;
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+    while let Some(state_id) = queue.pop_front() {
+        if state_id == target {
+            break;
+        }
+        for step in lts.0[state_id].steps.iter() {
+            if !visited[step.dst] {
+                visited[step.dst] = true;
+                parent[step.dst] = Some((state_id, step.label.clone()));
+                queue.push_back(step.dst);
+            }
+        }
+    }
+    let mut steps = vec![];
+    let mut state_id = target;
+    while let Some((prev_id, label)) = parent[state_id].clone() {
+        steps.push((label, state_id));
+        state_id = prev_id;
+    }
+    steps.reverse();
+    Trace { start: 0, steps }
+}
+
+use std::fmt;
+impl fmt::Display for Trace {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.start)?;
+        for (label, state_id) in self.steps.iter() {
+            write!(fmt, " -{}-> {}", label, state_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::process::Trans as ProcessTrans;
+
+    /// an lts over `i32` states numbered like the vectors, where the transitions are
+    /// `(label, dst, process)` and process 0 is at `A` in even states, `B` in odd ones.
+    pub(crate) fn lts(steps: Vec<Vec<(&str, StateId, Option<usize>)>>) -> Lts<i32> {
+        Lts(steps
+            .into_iter()
+            .enumerate()
+            .map(|(state_id, steps)| Trans {
+                state: State {
+                    vars: state_id as i32,
+                    locations: vec![Location::new(if state_id % 2 == 0 { "A" } else { "B" })],
+                    zone: None,
+                },
+                steps: steps
+                    .into_iter()
+                    .map(|(label, dst, process)| Step {
+                        label: Label::new(label),
+                        dst,
+                        process,
+                        weight: 1.0,
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// a process which takes `label` from `A` to `B`, then can only loop in `B` while
+    /// `x` is below 2, incrementing it.
+    fn process(label: &str) -> Process<i32> {
+        vec![
+            ExecUnit {
+                src: Location::new("A"),
+                invariant: vec![],
+                transs: vec![ProcessTrans {
+                    label: Label::new(label),
+                    dst: Location::new("B"),
+                    guard: |_| true,
+                    action: |x| x,
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+            ExecUnit {
+                src: Location::new("B"),
+                invariant: vec![],
+                transs: vec![ProcessTrans {
+                    label: Label::new(label),
+                    dst: Location::new("B"),
+                    guard: |x| *x < 2,
+                    action: |x| x + 1,
+                    clock_guard: vec![],
+                    resets: vec![],
+                }],
+            },
+        ]
+    }
+
+    #[test]
+    fn a_label_named_stutter_is_not_a_stutter_loop() {
+        let mut lts = Lts::concurrent_composition(0, vec![process(STUTTER)]).unwrap();
+        assert_eq!(lts.0.len(), 4);
+        assert_eq!(lts.terminal_states(), vec![3]);
+        lts.add_stutter_loops();
+        assert_eq!(lts.terminal_states(), vec![3]);
+        assert!(!lts.0[0].is_terminal());
+        assert_eq!(lts.0[3].steps[0].process, None);
+    }
+
+    #[test]
+    fn processes_are_told_apart_by_index() {
+        // both processes have the same locations, labels and transitions
+        let lts = Lts::concurrent_composition(0, vec![process("a"), process("a")]).unwrap();
+        assert!(lts.0[0].is_enabled(0) && lts.0[0].is_enabled(1));
+        let processes: Vec<Option<usize>> = lts.0[0].steps.iter().map(|s| s.process).collect();
+        assert_eq!(processes, vec![Some(0), Some(1)]);
+        assert_eq!(
+            lts.0[lts.0[0].steps[1].dst].state.locations[0],
+            Location::new("A")
+        );
+    }
+
+    #[test]
+    fn hide_renames_to_tau() {
+        let mut lts = Lts::concurrent_composition(0, vec![process("a"), process("b")]).unwrap();
+        lts.hide(&[Label::new("a")]);
+        let labels: Vec<&str> = lts.0[0].steps.iter().map(|s| s.label.0.as_str()).collect();
+        assert_eq!(labels, vec![TAU, "b"]);
+    }
+
+    #[test]
+    fn processes_may_not_use_tau() {
+        let err = Lts::concurrent_composition(0, vec![process("a"), process(TAU)]).unwrap_err();
+        assert_eq!(err, "label tau is reserved for hidden transitions");
+    }
+}
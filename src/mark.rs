@@ -1,8 +1,10 @@
 use crate::{
-    formula::{Formula, Prop},
-    lts::{Lts, StateId, Trans},
+    formula::{Actions, Formula, Prop},
+    graph,
+    lts::{Lts, State, StateId, Trace, Trans},
+    process::Label,
 };
-use std::collections::VecDeque;
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
 
 #[derive(Clone, Copy)]
 pub struct Mark(pub usize);
@@ -35,6 +37,64 @@ pub struct Marks {
     pub marks: Vec<Mark>,
 }
 
+impl Marks {
+    /// whether the whole specification holds at the state.
+    pub fn holds_at(&self, state_id: StateId) -> bool {
+        self.marks[state_id].is_marked(self.subformulas.len() - 1)
+    }
+}
+
+/// How the temporal operators treat states without successors.
+///
+/// CTL is defined over total transition relations, but an `Lts` built by
+/// `concurrent_composition` may contain deadlocked states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deadlock {
+    /// take every operator as its fixpoint over the transitions as they are: `EX f` and
+    /// `EG f` need a successor, so `EG f` never holds at a terminal state, while `AX f` and
+    /// `A[f U g]` hold there vacuously once `f` does. the default.
+    Fixpoint,
+    /// add a stuttering self-loop to every terminal state before marking.
+    Stutter,
+    /// quantify over maximal paths, which may be finite and end in a terminal state:
+    /// `EG f` holds at a terminal `f`-state, and `A[f U g]` fails at a terminal state
+    /// without `g`.
+    Finite,
+    /// fail if the lts has any terminal state.
+    Reject,
+}
+
+/// A constraint restricting the path quantifiers to fair paths.
+#[derive(Debug, Clone)]
+pub enum Fairness {
+    /// states satisfying the formula are visited infinitely often.
+    States(Formula),
+    /// a process which is enabled continuously from some point on fires infinitely often.
+    Weak(usize),
+    /// a process which is enabled infinitely often fires infinitely often.
+    Strong(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub deadlock: Deadlock,
+    /// with constraints, `EX`, `EU` and `EG` only consider fair paths (Fair CTL).
+    pub fairness: Vec<Fairness>,
+    /// for each process, the class of each state by what the process observes of it,
+    /// as computed by `observe`. needed by the epistemic operators.
+    pub observations: Vec<Vec<usize>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            deadlock: Deadlock::Fixpoint,
+            fairness: vec![],
+            observations: vec![],
+        }
+    }
+}
+
 use bimap::BiMap;
 use std::hash::Hash;
 pub fn make_marks<T: Clone + Hash + Eq>(
@@ -42,28 +102,167 @@ pub fn make_marks<T: Clone + Hash + Eq>(
     prop_valuate: fn(&Prop, &T) -> bool,
     spec: Formula,
 ) -> Marks {
+    make_marks_with(lts, prop_valuate, spec, &Options::default())
+        .expect("marking with the default options never fails")
+}
+
+pub fn make_marks_with<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    spec: Formula,
+    options: &Options,
+) -> Result<Marks, String> {
+    let mut looped = apply_deadlock(lts, options.deadlock)?;
+    let lts = looped.as_mut().unwrap_or(lts);
+
+    let constraints = fairness_constraints(lts, prop_valuate, options)?;
+    let fair = if constraints.is_empty() {
+        vec![true; lts.0.len()]
+    } else {
+        let all = vec![true; lts.0.len()];
+        fair_eg_states(lts, options.deadlock, &all, &constraints, &Actions::Any)
+    };
+
     let subformulas = spec.unfold();
+    for (_, f) in subformulas.iter() {
+        let group = match f {
+            Formula::K(i, _) => vec![*i],
+            Formula::EK(group, _) | Formula::CK(group, _) => group.clone(),
+            _ => continue,
+        };
+        for i in group {
+            match options.observations.get(i) {
+                Some(classes) if classes.len() == lts.0.len() => {}
+                Some(_) => return Err(format!("observations of process {} do not fit the lts", i)),
+                None => return Err(format!("no observations of process {} for {}", i, f)),
+            }
+        }
+    }
     let mut marks: Vec<Mark> = vec![Mark::empty(); lts.0.len()];
 
     for i in 0..subformulas.len() {
-        mark_impl(&mut marks, lts, prop_valuate, i, &subformulas);
+        mark_impl(
+            &mut marks,
+            lts,
+            prop_valuate,
+            options,
+            (&constraints, &fair),
+            i,
+            &subformulas,
+        );
+    }
+    Ok(Marks { subformulas, marks })
+}
+
+/// the states where `f` holds over maximal paths. `f` must not be epistemic.
+pub(crate) fn satisfying<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    f: Formula,
+) -> Result<Vec<bool>, String> {
+    reject_epistemic(&f)?;
+    let options = Options {
+        deadlock: Deadlock::Finite,
+        ..Default::default()
+    };
+    let marks = make_marks_with(lts, prop_valuate, f, &options)?;
+    Ok((0..lts.0.len()).map(|id| marks.holds_at(id)).collect())
+}
+
+/// fails on the epistemic operators, which only `make_marks_with` can check, given the
+/// observations of the processes.
+pub(crate) fn reject_epistemic(f: &Formula) -> Result<(), String> {
+    let epistemic = f
+        .clone()
+        .unfold()
+        .into_iter()
+        .find(|(_, g)| matches!(g, Formula::K(_, _) | Formula::EK(_, _) | Formula::CK(_, _)));
+    match epistemic {
+        Some((_, g)) => Err(format!(
+            "epistemic operator in {}: only supported by mark::make_marks_with",
+            g
+        )),
+        None => Ok(()),
+    }
+}
+
+/// checks the lts against the deadlock semantics. under `Deadlock::Stutter` with terminal
+/// states, returns a copy of the lts with their stuttering loops to check instead, leaving
+/// the lts as it is for the following checks.
+pub(crate) fn apply_deadlock<T: Clone + Hash + Eq>(
+    lts: &Lts<T>,
+    deadlock: Deadlock,
+) -> Result<Option<Lts<T>>, String> {
+    match deadlock {
+        Deadlock::Stutter if lts.0.iter().any(|trans| trans.steps.is_empty()) => {
+            let mut looped = lts.clone();
+            looped.add_stutter_loops();
+            return Ok(Some(looped));
+        }
+        Deadlock::Stutter | Deadlock::Fixpoint | Deadlock::Finite => {}
+        Deadlock::Reject => {
+            if let Some(state_id) = lts.terminal_states().first() {
+                return Err(format!("state {} has no successor", state_id));
+            }
+        }
     }
-    Marks {
-        subformulas: subformulas,
-        marks: marks,
+    Ok(None)
+}
+
+/// fairness constraints with their state formulas evaluated on the lts.
+enum Constraint {
+    States(Vec<bool>),
+    Weak(usize),
+    Strong(usize),
+}
+
+fn fairness_constraints<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    options: &Options,
+) -> Result<Vec<Constraint>, String> {
+    let mut constraints = vec![];
+    for fairness in options.fairness.iter() {
+        constraints.push(match fairness {
+            Fairness::States(f) => {
+                let options = Options {
+                    fairness: vec![],
+                    ..options.clone()
+                };
+                let marks = make_marks_with(lts, prop_valuate, f.clone(), &options)?;
+                Constraint::States((0..lts.0.len()).map(|id| marks.holds_at(id)).collect())
+            }
+            Fairness::Weak(process) => Constraint::Weak(*process),
+            Fairness::Strong(process) => Constraint::Strong(*process),
+        });
     }
+    Ok(constraints)
 }
 
 fn mark_impl<T: Clone + Hash + Eq>(
-    marks: &mut Vec<Mark>,
+    marks: &mut [Mark],
     lts: &mut Lts<T>,
     prop_valuate: fn(&Prop, &T) -> bool,
+    options: &Options,
+    (constraints, fair): (&[Constraint], &[bool]),
     i: usize,
     subformulas: &BiMap<usize, Formula>,
 ) {
     use Formula::*;
     match subformulas.get_by_left(&i).unwrap() {
+        True => update_mark(marks, lts, i, |_, _| true),
+        False => {}
         Prop(ref p) => update_mark(marks, lts, i, |_, trans| prop_valuate(p, &trans.state.vars)),
+        At(ref process, ref loc) => update_mark(marks, lts, i, |_, trans| {
+            trans.state.locations.get(*process) == Some(loc)
+        }),
+        Deadlock => update_mark(marks, lts, i, |_, trans| trans.is_terminal()),
+        Enabled(ref label) => update_mark(marks, lts, i, |_, trans| {
+            trans
+                .steps
+                .iter()
+                .any(|step| step.process.is_some() && &step.label == label)
+        }),
         Not(ref f) => {
             let f_index = subformulas.get_by_right(f).unwrap();
             let need_update_ids =
@@ -106,9 +305,9 @@ fn mark_impl<T: Clone + Hash + Eq>(
             let f_index = subformulas.get_by_right(f).unwrap();
             let need_update_ids = lts.find_states(|_, trans| {
                 trans
-                    .dst
+                    .steps
                     .iter()
-                    .any(|(_, succ_id)| marks[*succ_id].is_marked(*f_index))
+                    .any(|step| marks[step.dst].is_marked(*f_index) && fair[step.dst])
             });
             for state_id in need_update_ids {
                 marks[state_id].mark(i)
@@ -118,21 +317,17 @@ fn mark_impl<T: Clone + Hash + Eq>(
             let f1_index = subformulas.get_by_right(f1).unwrap();
             let f2_index = subformulas.get_by_right(f2).unwrap();
 
-            let mut need_update_ids =
-                lts.find_states(|state_id, _| marks[state_id].is_marked(*f2_index));
+            let mut need_update_ids = lts
+                .find_states(|state_id, _| marks[state_id].is_marked(*f2_index) && fair[state_id]);
             let mut queue = VecDeque::from(need_update_ids.clone());
-            loop {
-                if let Some(eu_id) = queue.pop_front() {
-                    let mut founds = lts.find_states(|state_id, trans| {
-                        trans.dst.iter().any(|(_, x)| *x == eu_id)
-                            && marks[state_id].is_marked(*f1_index)
-                            && !need_update_ids.iter().any(|x| *x == state_id) // not already exists
-                    });
-                    queue.append(&mut VecDeque::from(founds.clone()));
-                    need_update_ids.append(&mut founds);
-                } else {
-                    break;
-                }
+            while let Some(eu_id) = queue.pop_front() {
+                let mut founds = lts.find_states(|state_id, trans| {
+                    trans.steps.iter().any(|step| step.dst == eu_id)
+                        && marks[state_id].is_marked(*f1_index)
+                        && !need_update_ids.contains(&state_id) // not already exists
+                });
+                queue.append(&mut VecDeque::from(founds.clone()));
+                need_update_ids.append(&mut founds);
             }
             for state_id in need_update_ids {
                 marks[state_id].mark(i)
@@ -140,40 +335,535 @@ fn mark_impl<T: Clone + Hash + Eq>(
         }
         EG(box f) => {
             let f_index = subformulas.get_by_right(f).unwrap();
-            let mut need_update_ids =
-                lts.find_states(|state_id, _| marks[state_id].is_marked(*f_index));
-
-            // calc gfp
-            loop {
-                let unmark_ids: Vec<_> = need_update_ids
-                    .clone()
-                    .into_iter()
-                    .filter(|state_id| {
-                        let trans: &Trans<T> = lts.0.get(*state_id).unwrap();
-                        !trans
-                            .dst
-                            .iter()
-                            .any(|(_, next_id)| need_update_ids.contains(next_id))
+            for state_id in eg_states(marks, lts, options, constraints, *f_index, &Actions::Any) {
+                marks[state_id].mark(i)
+            }
+        }
+        Diamond(ref a, box ref f) => {
+            let f_index = subformulas.get_by_right(f).unwrap();
+            let need_update_ids = lts.find_states(|_, trans| {
+                trans.steps.iter().any(|step| {
+                    a.contains(&step.label) && marks[step.dst].is_marked(*f_index) && fair[step.dst]
+                })
+            });
+            for state_id in need_update_ids {
+                marks[state_id].mark(i)
+            }
+        }
+        EUa(box ref f1, ref a, ref b, box ref f2) => {
+            let f1_index = subformulas.get_by_right(f1).unwrap();
+            let f2_index = subformulas.get_by_right(f2).unwrap();
+
+            // states which can finish with a `b`-step into `f2`
+            let mut need_update_ids = lts.find_states(|state_id, trans| {
+                marks[state_id].is_marked(*f1_index)
+                    && trans.steps.iter().any(|step| {
+                        b.contains(&step.label)
+                            && marks[step.dst].is_marked(*f2_index)
+                            && fair[step.dst]
                     })
+            });
+            let mut queue = VecDeque::from(need_update_ids.clone());
+            while let Some(eu_id) = queue.pop_front() {
+                let mut founds = lts.find_states(|state_id, trans| {
+                    trans
+                        .steps
+                        .iter()
+                        .any(|step| step.dst == eu_id && a.contains(&step.label))
+                        && marks[state_id].is_marked(*f1_index)
+                        && !need_update_ids.contains(&state_id) // not already exists
+                });
+                queue.append(&mut VecDeque::from(founds.clone()));
+                need_update_ids.append(&mut founds);
+            }
+            for state_id in need_update_ids {
+                marks[state_id].mark(i)
+            }
+        }
+        EGa(ref a, box ref f) => {
+            let f_index = subformulas.get_by_right(f).unwrap();
+            for state_id in eg_states(marks, lts, options, constraints, *f_index, a) {
+                marks[state_id].mark(i)
+            }
+        }
+        EY(box ref f) => {
+            let f_index = subformulas.get_by_right(f).unwrap();
+            // every state is reachable from state 0, so any predecessor will do.
+            // state 0 itself only has one when it lies on a cycle.
+            let preds = lts.predecessors();
+            let need_update_ids = lts.find_states(|state_id, _| {
+                preds[state_id]
+                    .iter()
+                    .any(|pred_id| marks[*pred_id].is_marked(*f_index))
+            });
+            for state_id in need_update_ids {
+                marks[state_id].mark(i)
+            }
+        }
+        EO(box ref f) => {
+            let f_index = subformulas.get_by_right(f).unwrap();
+            let starts = lts.find_states(|state_id, _| marks[state_id].is_marked(*f_index));
+            for state_id in forward_reach(lts, starts, |_| true) {
+                marks[state_id].mark(i)
+            }
+        }
+        EH(box ref f) => {
+            let f_index = subformulas.get_by_right(f).unwrap();
+            if !lts.0.is_empty() && marks[0].is_marked(*f_index) {
+                for state_id in forward_reach(lts, vec![0], |id| marks[id].is_marked(*f_index)) {
+                    marks[state_id].mark(i)
+                }
+            }
+        }
+        ES(box ref f1, box ref f2) => {
+            let f1_index = subformulas.get_by_right(f1).unwrap();
+            let f2_index = subformulas.get_by_right(f2).unwrap();
+            let starts = lts.find_states(|state_id, _| marks[state_id].is_marked(*f2_index));
+            for state_id in forward_reach(lts, starts, |id| marks[id].is_marked(*f1_index)) {
+                marks[state_id].mark(i)
+            }
+        }
+        EUb(k, box ref f1, box ref f2) => {
+            let f1_index = *subformulas.get_by_right(f1).unwrap();
+            let f2_index = *subformulas.get_by_right(f2).unwrap();
+            let levels = eu_levels(marks, lts, fair, *k, f1_index, f2_index);
+            mark_last_level(marks, i, levels);
+        }
+        AUb(k, box ref f1, box ref f2) => {
+            let f1_index = *subformulas.get_by_right(f1).unwrap();
+            let f2_index = *subformulas.get_by_right(f2).unwrap();
+            // only fair paths count, so a state without any satisfies it vacuously
+            let init = (0..lts.0.len())
+                .map(|id| marks[id].is_marked(f2_index) || !fair[id])
+                .collect();
+            let levels = bounded_levels(*k, init, |state_id, prev| {
+                let trans = &lts.0[state_id];
+                let fair_succs: Vec<StateId> = trans
+                    .steps
+                    .iter()
+                    .map(|step| step.dst)
+                    .filter(|next_id| fair[*next_id])
                     .collect();
-                if unmark_ids.is_empty() {
-                    // already at fixed point
+                // as `AX`, the fixpoint holds at a terminal state, where a maximal path ends
+                let vacuous =
+                    options.deadlock == self::Deadlock::Fixpoint && trans.steps.is_empty();
+                prev[state_id]
+                    || (marks[state_id].is_marked(f1_index)
+                        && (vacuous || !fair_succs.is_empty())
+                        && fair_succs.iter().all(|next_id| prev[*next_id]))
+            });
+            mark_last_level(marks, i, levels);
+        }
+        EGb(k, box ref f) => {
+            let f_index = *subformulas.get_by_right(f).unwrap();
+            let levels = eg_levels(marks, lts, options.deadlock, fair, *k, f_index);
+            mark_last_level(marks, i, levels);
+        }
+        K(process, box ref f) => {
+            let f_index = *subformulas.get_by_right(f).unwrap();
+            let classes = &options.observations[*process];
+            for (state_id, known) in knows(marks, classes, f_index).into_iter().enumerate() {
+                if known {
+                    marks[state_id].mark(i)
+                }
+            }
+        }
+        EK(ref group, box ref f) => {
+            let f_index = *subformulas.get_by_right(f).unwrap();
+            let mut everyone = vec![true; lts.0.len()];
+            for process in group {
+                let classes = &options.observations[*process];
+                for (state_id, known) in knows(marks, classes, f_index).into_iter().enumerate() {
+                    everyone[state_id] &= known;
+                }
+            }
+            for state_id in lts.find_states(|state_id, _| everyone[state_id]) {
+                marks[state_id].mark(i)
+            }
+        }
+        CK(ref group, box ref f) => {
+            let f_index = *subformulas.get_by_right(f).unwrap();
+            // the states reachable through the indistinguishability of any process of the
+            // group share a component, where `f` must hold everywhere.
+            let mut component: Vec<usize> = (0..lts.0.len()).collect();
+            fn find(component: &mut [usize], x: usize) -> usize {
+                let mut root = x;
+                while component[root] != root {
+                    root = component[root];
+                }
+                component[x] = root;
+                root
+            }
+            for process in group {
+                let mut first_of_class = HashMap::new();
+                for (state_id, class) in options.observations[*process].iter().enumerate() {
+                    let first = *first_of_class.entry(class).or_insert(state_id);
+                    let (a, b) = (find(&mut component, first), find(&mut component, state_id));
+                    component[a] = b;
+                }
+            }
+            let classes: Vec<usize> = (0..lts.0.len())
+                .map(|state_id| find(&mut component, state_id))
+                .collect();
+            // with nobody to know anything, `C_G f` holds vacuously
+            let common = if group.is_empty() {
+                vec![true; lts.0.len()]
+            } else {
+                knows(marks, &classes, f_index)
+            };
+            for state_id in lts.find_states(|state_id, _| common[state_id]) {
+                marks[state_id].mark(i)
+            }
+        }
+    }
+}
+
+/// The class of each state by what `observation` sees of it, to set in `Options::observations`.
+pub fn observe<T, O: Hash + Eq>(lts: &Lts<T>, observation: impl Fn(&State<T>) -> O) -> Vec<usize> {
+    let mut ids = HashMap::new();
+    lts.0
+        .iter()
+        .map(|trans| {
+            let next = ids.len();
+            *ids.entry(observation(&trans.state)).or_insert(next)
+        })
+        .collect()
+}
+
+/// the states whose whole class satisfies the `f_index`-th subformula.
+fn knows(marks: &[Mark], classes: &[usize], f_index: usize) -> Vec<bool> {
+    let count = classes.iter().max().map_or(0, |c| c + 1);
+    let mut all = vec![true; count];
+    for (state_id, class) in classes.iter().enumerate() {
+        all[*class] &= marks[state_id].is_marked(f_index);
+    }
+    classes.iter().map(|class| all[*class]).collect()
+}
+
+/// `levels[j]` is the set of states satisfying the bounded operator with bound `j`,
+/// where `levels[j + 1]` is computed from `levels[j]` by `step`.
+/// stops early when the sets stabilize.
+fn bounded_levels(
+    k: usize,
+    init: Vec<bool>,
+    step: impl Fn(StateId, &[bool]) -> bool,
+) -> Vec<Vec<bool>> {
+    let mut levels = vec![init];
+    for _ in 0..k {
+        let prev = levels.last().unwrap();
+        let next: Vec<bool> = (0..prev.len()).map(|id| step(id, prev)).collect();
+        if next == *prev {
+            break;
+        }
+        levels.push(next);
+    }
+    levels
+}
+
+fn eu_levels<T>(
+    marks: &[Mark],
+    lts: &Lts<T>,
+    fair: &[bool],
+    k: usize,
+    f1_index: usize,
+    f2_index: usize,
+) -> Vec<Vec<bool>> {
+    let init = (0..lts.0.len())
+        .map(|id| marks[id].is_marked(f2_index) && fair[id])
+        .collect();
+    bounded_levels(k, init, |state_id, prev| {
+        prev[state_id]
+            || (marks[state_id].is_marked(f1_index)
+                && lts.0[state_id].steps.iter().any(|step| prev[step.dst]))
+    })
+}
+
+fn eg_levels<T>(
+    marks: &[Mark],
+    lts: &Lts<T>,
+    deadlock: Deadlock,
+    fair: &[bool],
+    k: usize,
+    f_index: usize,
+) -> Vec<Vec<bool>> {
+    let init = (0..lts.0.len())
+        .map(|id| marks[id].is_marked(f_index) && fair[id])
+        .collect();
+    bounded_levels(k, init, |state_id, prev| {
+        let trans = &lts.0[state_id];
+        let can_stop = can_stop(trans, deadlock, &Actions::Any) && fair[state_id];
+        marks[state_id].is_marked(f_index)
+            && (can_stop || trans.steps.iter().any(|step| prev[step.dst]))
+    })
+}
+
+fn mark_last_level(marks: &mut [Mark], i: usize, mut levels: Vec<Vec<bool>>) {
+    for (state_id, sat) in levels.pop().unwrap().into_iter().enumerate() {
+        if sat {
+            marks[state_id].mark(i)
+        }
+    }
+}
+
+/// A shortest path from the state witnessing the specification, when its outermost
+/// operator is `EX`, `EU`, `EUb` or `EGb`. fairness constraints are not taken into account.
+pub fn witness<T>(lts: &Lts<T>, marks: &Marks, state_id: StateId) -> Option<Trace> {
+    use Formula::*;
+    if !marks.holds_at(state_id) {
+        return None;
+    }
+    let index_of = |f: &Formula| *marks.subformulas.get_by_right(f).unwrap();
+    let spec = marks
+        .subformulas
+        .get_by_left(&(marks.subformulas.len() - 1))?;
+    let mut trace = Trace {
+        start: state_id,
+        steps: vec![],
+    };
+    match spec {
+        EX(box f) => {
+            let f_index = index_of(f);
+            let step = lts.0[state_id]
+                .steps
+                .iter()
+                .find(|step| marks.marks[step.dst].is_marked(f_index))?;
+            trace.steps.push((step.label.clone(), step.dst));
+        }
+        EU(box f1, box f2) | EUb(_, box f1, box f2) => {
+            let bound = match spec {
+                EUb(k, _, _) => *k,
+                _ => usize::MAX,
+            };
+            let (f1_index, f2_index) = (index_of(f1), index_of(f2));
+            // breadth first search through `f1`-states for a `f2`-state
+            let mut parent: HashMap<StateId, Option<(StateId, Label)>> = HashMap::new();
+            parent.insert(state_id, None);
+            let mut queue = VecDeque::from(vec![(state_id, 0)]);
+            let mut goal = None;
+            while let Some((id, depth)) = queue.pop_front() {
+                if marks.marks[id].is_marked(f2_index) {
+                    goal = Some(id);
                     break;
                 }
-                for unmark_id in unmark_ids {
-                    need_update_ids.retain(|x| *x != unmark_id);
+                if depth == bound || !marks.marks[id].is_marked(f1_index) {
+                    continue;
+                }
+                for step in lts.0[id].steps.iter() {
+                    if let Entry::Vacant(entry) = parent.entry(step.dst) {
+                        entry.insert(Some((id, step.label.clone())));
+                        queue.push_back((step.dst, depth + 1));
+                    }
+                }
+            }
+            let mut id = goal?;
+            while let Some(Some((prev_id, label))) = parent.get(&id) {
+                trace.steps.push((label.clone(), id));
+                id = *prev_id;
+            }
+            trace.steps.reverse();
+        }
+        EGb(k, box f) => {
+            let f_index = index_of(f);
+            let fair = vec![true; lts.0.len()];
+            let levels_for = |deadlock| eg_levels(&marks.marks, lts, deadlock, &fair, *k, f_index);
+            // a path of `k` steps if there is one, else one stopping at a terminal state
+            let mut levels = levels_for(self::Deadlock::Fixpoint);
+            if !levels.last().unwrap()[state_id] {
+                levels = levels_for(self::Deadlock::Finite);
+            }
+            let mut id = state_id;
+            for j in (0..*k).rev() {
+                let level = &levels[j.min(levels.len() - 1)];
+                match lts.0[id].steps.iter().find(|step| level[step.dst]) {
+                    Some(step) => {
+                        trace.steps.push((step.label.clone(), step.dst));
+                        id = step.dst;
+                    }
+                    None => break, // stopped at a terminal state
                 }
             }
+        }
+        _ => return None,
+    }
+    Some(trace)
+}
 
-            for state_id in need_update_ids {
-                marks[state_id].mark(i)
+/// states reachable from `starts` through states satisfying `within`, including `starts`.
+fn forward_reach<T>(
+    lts: &Lts<T>,
+    starts: Vec<StateId>,
+    within: impl Fn(StateId) -> bool,
+) -> Vec<StateId> {
+    let mut reached = vec![false; lts.0.len()];
+    for state_id in starts.iter() {
+        reached[*state_id] = true;
+    }
+    let mut queue = VecDeque::from(starts);
+    while let Some(state_id) = queue.pop_front() {
+        for step in lts.0[state_id].steps.iter() {
+            if !reached[step.dst] && within(step.dst) {
+                reached[step.dst] = true;
+                queue.push_back(step.dst);
             }
         }
     }
+    (0..lts.0.len()).filter(|id| reached[*id]).collect()
+}
+
+/// states from which a maximal path of `actions`-transitions stays in `f_index`-states.
+fn eg_states<T>(
+    marks: &[Mark],
+    lts: &Lts<T>,
+    options: &Options,
+    constraints: &[Constraint],
+    f_index: usize,
+    actions: &Actions,
+) -> Vec<StateId> {
+    if !constraints.is_empty() {
+        let within: Vec<bool> = marks.iter().map(|m| m.is_marked(f_index)).collect();
+        let fair = fair_eg_states(lts, options.deadlock, &within, constraints, actions);
+        return (0..lts.0.len()).filter(|id| fair[*id]).collect();
+    }
+
+    let mut need_update_ids: Vec<StateId> = (0..lts.0.len())
+        .filter(|state_id| marks[*state_id].is_marked(f_index))
+        .collect();
+
+    // calc gfp
+    loop {
+        let unmark_ids: Vec<_> = need_update_ids
+            .clone()
+            .into_iter()
+            .filter(|state_id| {
+                let trans: &Trans<T> = lts.0.get(*state_id).unwrap();
+                !can_stop(trans, options.deadlock, actions)
+                    && !trans.steps.iter().any(|step| {
+                        actions.contains(&step.label) && need_update_ids.contains(&step.dst)
+                    })
+            })
+            .collect();
+        if unmark_ids.is_empty() {
+            // already at fixed point
+            break;
+        }
+        for unmark_id in unmark_ids {
+            need_update_ids.retain(|x| *x != unmark_id);
+        }
+    }
+    need_update_ids
+}
+
+/// whether a maximal path of `actions`-transitions may stop at the state: under
+/// `Deadlock::Finite` and `Deadlock::Stutter`, when it has no such transition.
+fn can_stop<T>(trans: &Trans<T>, deadlock: Deadlock, actions: &Actions) -> bool {
+    match deadlock {
+        Deadlock::Finite | Deadlock::Stutter => {
+            !trans.steps.iter().any(|step| actions.contains(&step.label))
+        }
+        Deadlock::Fixpoint | Deadlock::Reject => false,
+    }
+}
+
+/// states from which a fair path of `actions`-transitions stays in `within`-states,
+/// found by searching fair strongly connected components (Emerson-Lei).
+///
+/// a path ending in a state where it may stop, by `can_stop`, is treated as stuttering
+/// there forever; otherwise only infinite paths count.
+fn fair_eg_states<T>(
+    lts: &Lts<T>,
+    deadlock: Deadlock,
+    within: &[bool],
+    constraints: &[Constraint],
+    actions: &Actions,
+) -> Vec<bool> {
+    let n = lts.0.len();
+    let succs = |state_id: StateId, inside: &[bool]| -> Vec<StateId> {
+        lts.0[state_id]
+            .steps
+            .iter()
+            .filter(|step| actions.contains(&step.label) && inside[step.dst])
+            .map(|step| step.dst)
+            .collect()
+    };
+    // whether the process fires on a transition inside the component
+    let fires_in = |component: &[StateId], inside: &[bool], process: usize| {
+        component.iter().any(|state_id| {
+            lts.0[*state_id].steps.iter().any(|step| {
+                step.process == Some(process) && actions.contains(&step.label) && inside[step.dst]
+            })
+        })
+    };
+
+    let mut targets = vec![];
+    let mut pending = vec![(0..n).filter(|id| within[*id]).collect::<Vec<_>>()];
+    while let Some(states) = pending.pop() {
+        let mut inside = vec![false; n];
+        for state_id in states.iter() {
+            inside[*state_id] = true;
+        }
+        for component in graph::sccs(n, &states, |v| succs(v, &inside)) {
+            let terminal =
+                component.len() == 1 && can_stop(&lts.0[component[0]], deadlock, actions);
+            if !terminal && !graph::is_nontrivial(&component, |v| succs(v, &inside)) {
+                continue;
+            }
+            let mut in_component = vec![false; n];
+            for state_id in component.iter() {
+                in_component[*state_id] = true;
+            }
+            let mut is_fair = true;
+            let mut strong_unfair = None;
+            for constraint in constraints {
+                match constraint {
+                    Constraint::States(set) => {
+                        is_fair &= component.iter().any(|state_id| set[*state_id]);
+                    }
+                    Constraint::Weak(process) => {
+                        is_fair &= fires_in(&component, &in_component, *process)
+                            || component
+                                .iter()
+                                .any(|state_id| !lts.0[*state_id].is_enabled(*process));
+                    }
+                    Constraint::Strong(process) => {
+                        if !fires_in(&component, &in_component, *process)
+                            && component
+                                .iter()
+                                .any(|state_id| lts.0[*state_id].is_enabled(*process))
+                        {
+                            strong_unfair = Some(*process);
+                        }
+                    }
+                }
+            }
+            if !is_fair {
+                continue;
+            }
+            match strong_unfair {
+                // a fair path has to avoid the states enabling the process
+                Some(process) => pending.push(
+                    component
+                        .into_iter()
+                        .filter(|state_id| !lts.0[*state_id].is_enabled(process))
+                        .collect(),
+                ),
+                None => targets.extend(component),
+            }
+        }
+    }
+
+    let mut preds = vec![vec![]; n];
+    for (state_id, trans) in lts.0.iter().enumerate() {
+        for step in trans.steps.iter() {
+            if actions.contains(&step.label) {
+                preds[step.dst].push(state_id);
+            }
+        }
+    }
+    graph::backward_reach(n, &targets, |v| preds[v].clone(), |v| within[v])
 }
 
 fn update_mark<T>(
-    marks: &mut Vec<Mark>,
+    marks: &mut [Mark],
     lts: &Lts<T>,
     i: usize,
     pred: impl Fn(StateId, &Trans<T>) -> bool,
@@ -184,3 +874,335 @@ fn update_mark<T>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lts::{tests::lts, STUTTER};
+    use crate::process::Location;
+
+    fn holds(lts: &mut Lts<i32>, f: Formula, options: &Options) -> Vec<bool> {
+        let marks = make_marks_with(lts, |_, _| false, f, options).unwrap();
+        (0..lts.0.len()).map(|id| marks.holds_at(id)).collect()
+    }
+
+    #[test]
+    fn builtin_propositions() {
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0))],
+            vec![(STUTTER, 1, None)],
+            vec![(STUTTER, 2, Some(0))],
+        ]);
+        let options = Options::default();
+        let at_b = Formula::At(0, Location::new("B"));
+        assert_eq!(holds(&mut lts, at_b, &options), vec![false, true, false]);
+        let deadlock = Formula::Deadlock;
+        assert_eq!(
+            holds(&mut lts, deadlock, &options),
+            vec![false, true, false]
+        );
+        let enabled = Formula::Enabled(Label::new(STUTTER));
+        assert_eq!(holds(&mut lts, enabled, &options), vec![false, false, true]);
+    }
+
+    fn with(deadlock: Deadlock) -> Options {
+        Options {
+            deadlock,
+            ..Default::default()
+        }
+    }
+
+    /// `0 -a-> 1`, where 1 is terminal.
+    fn deadlocked() -> Lts<i32> {
+        lts(vec![vec![("a", 1, Some(0))], vec![]])
+    }
+
+    #[test]
+    fn deadlock_fixpoint_is_the_default() {
+        let options = Options::default();
+        assert_eq!(options.deadlock, Deadlock::Fixpoint);
+        let eg = Formula::EG(Box::new(Formula::True));
+        assert_eq!(holds(&mut deadlocked(), eg, &options), vec![false, false]);
+        let ex = Formula::EX(Box::new(Formula::True));
+        assert_eq!(holds(&mut deadlocked(), ex, &options), vec![true, false]);
+        // `AX false` and `A[true U[<=2] false]` hold vacuously at the deadlock
+        let ax = Formula::Not(Box::new(Formula::EX(Box::new(Formula::True))));
+        assert_eq!(holds(&mut deadlocked(), ax, &options), vec![false, true]);
+        let au = Formula::AUb(2, Box::new(Formula::True), Box::new(Formula::False));
+        assert_eq!(holds(&mut deadlocked(), au, &options), vec![true, true]);
+    }
+
+    #[test]
+    fn deadlock_finite_ends_maximal_paths() {
+        let options = with(Deadlock::Finite);
+        let eg = Formula::EG(Box::new(Formula::True));
+        assert_eq!(holds(&mut deadlocked(), eg, &options), vec![true, true]);
+        let egb = Formula::EGb(3, Box::new(Formula::True));
+        assert_eq!(holds(&mut deadlocked(), egb, &options), vec![true, true]);
+        let ex = Formula::EX(Box::new(Formula::True));
+        assert_eq!(holds(&mut deadlocked(), ex, &options), vec![true, false]);
+        let au = Formula::AUb(2, Box::new(Formula::True), Box::new(Formula::False));
+        assert_eq!(holds(&mut deadlocked(), au, &options), vec![false, false]);
+    }
+
+    #[test]
+    fn deadlock_stutter_loops_terminal_states() {
+        let options = with(Deadlock::Stutter);
+        let mut lts = deadlocked();
+        let ex = Formula::EX(Box::new(Formula::Deadlock));
+        assert_eq!(holds(&mut lts, ex, &options), vec![true, true]);
+        let eg = Formula::EG(Box::new(Formula::True));
+        assert_eq!(holds(&mut lts, eg, &options), vec![true, true]);
+        // the loops are not left in the lts for the following checks
+        assert_eq!(lts.terminal_states(), vec![1]);
+        assert!(lts.0[1].steps.is_empty());
+        let ex = Formula::EX(Box::new(Formula::True));
+        assert_eq!(
+            holds(&mut lts, ex.clone(), &Options::default()),
+            vec![true, false]
+        );
+        assert_eq!(
+            satisfying(&mut lts, |_, _| false, ex),
+            Ok(vec![true, false])
+        );
+    }
+
+    #[test]
+    fn deadlock_reject_fails() {
+        let result = make_marks_with(
+            &mut deadlocked(),
+            |_, _| false,
+            Formula::True,
+            &with(Deadlock::Reject),
+        );
+        assert_eq!(result.unwrap_err(), "state 1 has no successor");
+    }
+
+    #[test]
+    fn fairness_excludes_starving_loops() {
+        // process 0 may loop on 0 forever while process 1 is enabled there
+        let steps = || {
+            vec![
+                vec![("a", 0, Some(0)), ("b", 1, Some(1))],
+                vec![("c", 1, Some(0))],
+            ]
+        };
+        let stays_at_a = || Formula::EG(Box::new(Formula::At(0, Location::new("A"))));
+        let ex = || Formula::EX(Box::new(Formula::True));
+        let options = Options::default();
+        assert_eq!(
+            holds(&mut lts(steps()), stays_at_a(), &options),
+            vec![true, false]
+        );
+        for fairness in [
+            Fairness::States(Formula::At(0, Location::new("B"))),
+            Fairness::Weak(1),
+            Fairness::Strong(1),
+        ] {
+            let options = Options {
+                fairness: vec![fairness],
+                ..Default::default()
+            };
+            assert_eq!(
+                holds(&mut lts(steps()), stays_at_a(), &options),
+                vec![false, false]
+            );
+            assert_eq!(holds(&mut lts(steps()), ex(), &options), vec![true, true]);
+        }
+    }
+
+    #[test]
+    fn fair_paths_end_at_deadlocks_only_when_finite() {
+        let eg = || Formula::EG(Box::new(Formula::True));
+        let mut options = with(Deadlock::Fixpoint);
+        options.fairness = vec![Fairness::States(Formula::True)];
+        assert_eq!(holds(&mut deadlocked(), eg(), &options), vec![false, false]);
+        options.deadlock = Deadlock::Finite;
+        assert_eq!(holds(&mut deadlocked(), eg(), &options), vec![true, true]);
+    }
+
+    #[test]
+    fn egb_witness_prefers_full_paths() {
+        // 0 can stop at the deadlock 1 or loop on 2
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![],
+            vec![("c", 2, Some(0))],
+        ]);
+        let egb = Formula::EGb(3, Box::new(Formula::True));
+        let marks = make_marks_with(&mut lts, |_, _| false, egb, &Options::default()).unwrap();
+        let trace = witness(&lts, &marks, 0).unwrap();
+        assert_eq!(trace.to_string(), "0 -b-> 2 -c-> 2 -c-> 2");
+    }
+
+    #[test]
+    fn past_operators_over_paths_from_the_initial_state() {
+        // 0 -a-> 1 -c-> 3 -e-> 4 and 0 -b-> 2 -d-> 3, at `B` in 1 and 3
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![("c", 3, Some(0))],
+            vec![("d", 3, Some(0))],
+            vec![("e", 4, Some(0))],
+            vec![],
+        ]);
+        let at = |location| Box::new(Formula::At(0, Location::new(location)));
+        let options = Options::default();
+        let ey = Formula::EY(at("B"));
+        assert_eq!(
+            holds(&mut lts, ey, &options),
+            vec![false, false, false, true, true]
+        );
+        let eo = Formula::EO(at("B"));
+        assert_eq!(
+            holds(&mut lts, eo, &options),
+            vec![false, true, false, true, true]
+        );
+        let eh = Formula::EH(at("A"));
+        assert_eq!(
+            holds(&mut lts, eh, &options),
+            vec![true, false, true, false, false]
+        );
+        let es = Formula::ES(at("A"), at("B"));
+        assert_eq!(
+            holds(&mut lts, es, &options),
+            vec![false, true, false, true, true]
+        );
+    }
+
+    #[test]
+    fn bounded_operators_count_steps() {
+        // 0 -a-> 1 -b-> 2 -c-> 3, and 0 -d-> 3, where 3 loops on `e`
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("d", 3, Some(0))],
+            vec![("b", 2, Some(0))],
+            vec![("c", 3, Some(0))],
+            vec![("e", 3, Some(0))],
+        ]);
+        let looping = || Box::new(Formula::Enabled(Label::new("e")));
+        let options = Options::default();
+        let eub = Formula::EUb(1, Box::new(Formula::True), looping());
+        assert_eq!(
+            holds(&mut lts, eub.clone(), &options),
+            vec![true, false, true, true]
+        );
+        let marks = make_marks_with(&mut lts, |_, _| false, eub, &options).unwrap();
+        assert_eq!(witness(&lts, &marks, 0).unwrap().to_string(), "0 -d-> 3");
+        let aub = Formula::AUb(1, Box::new(Formula::True), looping());
+        assert_eq!(
+            holds(&mut lts, aub, &options),
+            vec![false, false, true, true]
+        );
+        let aub = Formula::AUb(3, Box::new(Formula::True), looping());
+        assert_eq!(holds(&mut lts, aub, &options), vec![true, true, true, true]);
+        // `EG[<=k] !looping` needs `k` steps before reaching 3
+        let egb = |k| Formula::EGb(k, Box::new(Formula::Not(looping())));
+        assert_eq!(
+            holds(&mut lts, egb(1), &options),
+            vec![true, true, false, false]
+        );
+        assert_eq!(
+            holds(&mut lts, egb(2), &options),
+            vec![true, false, false, false]
+        );
+    }
+
+    fn of(label: &str) -> Actions {
+        Actions::Of(vec![Label::new(label)])
+    }
+
+    #[test]
+    fn action_modalities() {
+        // 0 -req-> 1 -ack-> 2 -req-> 1
+        let mut lts = lts(vec![
+            vec![("req", 1, Some(0))],
+            vec![("ack", 2, Some(0))],
+            vec![("req", 1, Some(0))],
+        ]);
+        let options = Options::default();
+        let can_ack = Formula::Diamond(of("ack"), Box::new(Formula::True));
+        assert_eq!(holds(&mut lts, can_ack, &options), vec![false, true, false]);
+        // `[req] <ack> true`
+        let acked = Formula::Not(Box::new(Formula::Diamond(
+            of("req"),
+            Box::new(Formula::Not(Box::new(Formula::Diamond(
+                of("ack"),
+                Box::new(Formula::True),
+            )))),
+        )));
+        assert_eq!(holds(&mut lts, acked, &options), vec![true, true, true]);
+        // `E[true {req} U {ack} true]`
+        let eua = Formula::EUa(
+            Box::new(Formula::True),
+            of("req"),
+            of("ack"),
+            Box::new(Formula::True),
+        );
+        assert_eq!(holds(&mut lts, eua, &options), vec![true, true, true]);
+    }
+
+    #[test]
+    fn ega_stops_without_actions() {
+        // 0 -a-> 1 -b-> 2, where 2 is terminal
+        let ega = || Formula::EGa(of("a"), Box::new(Formula::True));
+        let steps = || vec![vec![("a", 1, Some(0))], vec![("b", 2, Some(0))], vec![]];
+        let mut fixpoint = lts(steps());
+        let all_false = vec![false, false, false];
+        assert_eq!(holds(&mut fixpoint, ega(), &Options::default()), all_false);
+        for deadlock in [Deadlock::Finite, Deadlock::Stutter] {
+            let mut lts = lts(steps());
+            assert_eq!(
+                holds(&mut lts, ega(), &with(deadlock)),
+                vec![true, true, true]
+            );
+        }
+        let mut options = with(Deadlock::Stutter);
+        options.fairness = vec![Fairness::States(Formula::True)];
+        assert_eq!(
+            holds(&mut lts(steps()), ega(), &options),
+            vec![true, true, true]
+        );
+    }
+
+    #[test]
+    fn knowledge_over_observations() {
+        // process 0 only observes its location, and x is 2 at state 2 only
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![],
+            vec![],
+        ]);
+        let x_is_2 = |p: &Prop, x: &i32| p == "x=2" && *x == 2;
+        let k = |f| Formula::K(0, Box::new(f));
+        let at_a = Formula::At(0, Location::new("A"));
+        let options = Options {
+            observations: vec![observe(&lts, |state| state.locations.clone())],
+            ..Default::default()
+        };
+        let know = |lts: &mut Lts<i32>, f| {
+            let marks = make_marks_with(lts, x_is_2, f, &options).unwrap();
+            (0..lts.0.len())
+                .map(|id| marks.holds_at(id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(know(&mut lts, k(at_a)), vec![true, false, true]);
+        let x = Formula::Prop("x=2".to_string());
+        assert_eq!(know(&mut lts, k(x)), vec![false, false, false]);
+        let ck = Formula::CK(vec![0], Box::new(Formula::Deadlock));
+        assert_eq!(know(&mut lts, ck), vec![false, true, false]);
+        let missing = make_marks_with(&mut lts, x_is_2, k(Formula::True), &Options::default());
+        assert_eq!(
+            missing.unwrap_err(),
+            "no observations of process 0 for (K 0 true)"
+        );
+    }
+
+    #[test]
+    fn other_checkers_reject_epistemic_atoms() {
+        let k = Formula::K(0, Box::new(Formula::True));
+        let result = satisfying(&mut lts(vec![vec![]]), |_, _| false, k);
+        assert_eq!(
+            result.unwrap_err(),
+            "epistemic operator in (K 0 true): only supported by mark::make_marks_with"
+        );
+    }
+}
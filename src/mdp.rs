@@ -0,0 +1,348 @@
+use std::hash::Hash;
+
+use crate::{
+    dtmc,
+    formula::{Formula, Prop},
+    graph,
+    lts::{Lts, StateId, Trace, STUTTER},
+    mark,
+    process::Label,
+};
+
+/// bound on the change of the values at which value iteration stops.
+const EPSILON: f64 = 1e-12;
+
+const MAX_ITERATIONS: usize = 1_000_000;
+
+/// An action of a state of an `Mdp`: a process moving, and the distribution of the successors.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    /// the moving process, or `None` for the self-loop of a terminal state.
+    pub process: Option<usize>,
+    pub succs: Vec<(Label, StateId, f64)>,
+}
+
+/// A Markov decision process over the states of an `Lts`: which process moves is chosen
+/// nondeterministically, then the successor at random by the weights of its transitions.
+#[derive(Debug, Clone)]
+pub struct Mdp(pub Vec<Vec<Choice>>);
+
+impl Mdp {
+    /// terminal states have a single choice, looping on themselves.
+    pub fn new<T>(lts: &Lts<T>) -> Result<Mdp, String> {
+        let mut choices = vec![];
+        for (state_id, trans) in lts.0.iter().enumerate() {
+            let enabled = dtmc::enabled_processes(trans);
+            if enabled.is_empty() {
+                choices.push(vec![Choice {
+                    process: None,
+                    succs: vec![(Label::new(STUTTER), state_id, 1.0)],
+                }]);
+                continue;
+            }
+            let mut state_choices = vec![];
+            for process in enabled {
+                state_choices.push(Choice {
+                    process: Some(process),
+                    succs: dtmc::distribution(trans, state_id, process)?,
+                });
+            }
+            choices.push(state_choices);
+        }
+        Ok(Mdp(choices))
+    }
+}
+
+/// Whether a scheduler resolving the choices minimizes or maximizes the probability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    Min,
+    Max,
+}
+
+/// The optimal probabilities, with a scheduler achieving them.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub probs: Vec<f64>,
+    /// index in `Mdp` of the choice the scheduler takes at each state.
+    pub scheduler: Vec<usize>,
+    /// the states where the path formula is fulfilled.
+    pub goal: Vec<bool>,
+}
+
+impl Solution {
+    /// follows the scheduler from `start`, taking at each step the successor most likely
+    /// to lead to a goal, until a goal state, a terminal state or a state already visited.
+    pub fn trace(&self, mdp: &Mdp, start: StateId) -> Trace {
+        let mut trace = Trace {
+            start,
+            steps: vec![],
+        };
+        let mut visited = vec![false; mdp.0.len()];
+        let mut state_id = start;
+        while !self.goal[state_id] && !visited[state_id] {
+            visited[state_id] = true;
+            let choice = &mdp.0[state_id][self.scheduler[state_id]];
+            if choice.process.is_none() {
+                break;
+            }
+            let likelihood =
+                |(_, next_id, p): &&(Label, StateId, f64)| (p * self.probs[*next_id], *p);
+            let (label, next_id, _) = choice
+                .succs
+                .iter()
+                .max_by(|x, y| {
+                    let ((x0, x1), (y0, y1)) = (likelihood(x), likelihood(y));
+                    x0.total_cmp(&y0).then(x1.total_cmp(&y1))
+                })
+                .unwrap();
+            trace.steps.push((label.clone(), *next_id));
+            state_id = *next_id;
+        }
+        trace
+    }
+}
+
+/// `Pmin` or `Pmax` of reaching a state where `g` holds.
+pub fn reach<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    mdp: &Mdp,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    g: Formula,
+    objective: Objective,
+) -> Result<Solution, String> {
+    until(lts, mdp, prop_valuate, Formula::True, g, objective)
+}
+
+/// `Pmin` or `Pmax` of `f U g`, by value iteration.
+pub fn until<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    mdp: &Mdp,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    f: Formula,
+    g: Formula,
+    objective: Objective,
+) -> Result<Solution, String> {
+    if mdp.0.len() != lts.0.len() {
+        return Err("the mdp was not built from this lts".to_string());
+    }
+    let sat_f = mark::satisfying(lts, prop_valuate, f)?;
+    let sat_g = mark::satisfying(lts, prop_valuate, g)?;
+    Ok(solve_until(mdp, &sat_f, &sat_g, objective))
+}
+
+pub(crate) fn solve_until(
+    mdp: &Mdp,
+    sat_f: &[bool],
+    sat_g: &[bool],
+    objective: Objective,
+) -> Solution {
+    let n = mdp.0.len();
+    let positive = positive_states(mdp, sat_f, sat_g, objective);
+    let value = |probs: &[f64], choice: &Choice| -> f64 {
+        choice
+            .succs
+            .iter()
+            .fold(0.0, |acc, (_, next_id, p)| acc + p * probs[*next_id])
+    };
+    let best = |values: Vec<f64>| -> (usize, f64) {
+        let pick = values.iter().enumerate().reduce(|x, y| match objective {
+            Objective::Min if y.1 < x.1 => y,
+            Objective::Max if y.1 > x.1 => y,
+            _ => x,
+        });
+        pick.map(|(i, v)| (i, *v)).unwrap()
+    };
+
+    let mut probs: Vec<f64> = sat_g.iter().map(|g| if *g { 1.0 } else { 0.0 }).collect();
+    for _ in 0..MAX_ITERATIONS {
+        let mut change: f64 = 0.0;
+        for state_id in 0..n {
+            if sat_g[state_id] || !positive[state_id] {
+                continue;
+            }
+            let values = mdp.0[state_id].iter().map(|c| value(&probs, c)).collect();
+            let (_, v) = best(values);
+            change = change.max((v - probs[state_id]).abs());
+            probs[state_id] = v;
+        }
+        if change < EPSILON {
+            break;
+        }
+    }
+
+    let mut scheduler: Vec<usize> = (0..n)
+        .map(|state_id| best(mdp.0[state_id].iter().map(|c| value(&probs, c)).collect()).0)
+        .collect();
+    if objective == Objective::Max {
+        // among optimal choices, a maximizing scheduler must also make progress
+        // toward `g` rather than loop forever with equal values.
+        let mut settled = sat_g.to_vec();
+        loop {
+            let mut changed = false;
+            for state_id in 0..n {
+                if settled[state_id] || probs[state_id] <= 0.0 {
+                    continue;
+                }
+                let progress = mdp.0[state_id].iter().position(|choice| {
+                    (value(&probs, choice) - probs[state_id]).abs() < 1e3 * EPSILON
+                        && choice
+                            .succs
+                            .iter()
+                            .any(|(_, next_id, p)| *p > 0.0 && settled[*next_id])
+                });
+                if let Some(i) = progress {
+                    scheduler[state_id] = i;
+                    settled[state_id] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+    Solution {
+        probs,
+        scheduler,
+        goal: sat_g.to_vec(),
+    }
+}
+
+/// the states where the optimal probability of `f U g` is positive:
+/// for `Max` some choices reach `g`, for `Min` all of them must.
+fn positive_states(mdp: &Mdp, sat_f: &[bool], sat_g: &[bool], objective: Objective) -> Vec<bool> {
+    let mut positive = sat_g.to_vec();
+    loop {
+        let mut changed = false;
+        for (state_id, choices) in mdp.0.iter().enumerate() {
+            if positive[state_id] || !sat_f[state_id] {
+                continue;
+            }
+            let leads = |choice: &Choice| {
+                choice
+                    .succs
+                    .iter()
+                    .any(|(_, next_id, p)| *p > 0.0 && positive[*next_id])
+            };
+            let reached = match objective {
+                Objective::Max => choices.iter().any(leads),
+                Objective::Min => choices.iter().all(leads),
+            };
+            if reached {
+                positive[state_id] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    positive
+}
+
+/// the states where the `Max` (resp. `Min`) probability of reaching `g` is 1.
+pub(crate) fn almost_sure(mdp: &Mdp, sat_g: &[bool], objective: Objective) -> Vec<bool> {
+    let n = mdp.0.len();
+    let all = vec![true; n];
+    match objective {
+        Objective::Min => {
+            // some scheduler avoids `g` with a positive probability from where it can
+            // reach a state avoiding `g` forever.
+            let positive = positive_states(mdp, &all, sat_g, Objective::Min);
+            let avoiding: Vec<StateId> = (0..n).filter(|id| !positive[*id]).collect();
+            let mut preds = vec![vec![]; n];
+            for (state_id, choices) in mdp.0.iter().enumerate() {
+                for (_, next_id, p) in choices.iter().flat_map(|c| c.succs.iter()) {
+                    if *p > 0.0 {
+                        preds[*next_id].push(state_id);
+                    }
+                }
+            }
+            let can_avoid =
+                graph::backward_reach(n, &avoiding, |v| preds[v].clone(), |v| !sat_g[v]);
+            can_avoid.into_iter().map(|b| !b).collect()
+        }
+        Objective::Max => {
+            // greatest set of states with a choice staying inside the set and
+            // making progress toward `g`.
+            let mut stay = all;
+            loop {
+                let mut reached = sat_g.to_vec();
+                loop {
+                    let mut changed = false;
+                    for (state_id, choices) in mdp.0.iter().enumerate() {
+                        if reached[state_id] || !stay[state_id] {
+                            continue;
+                        }
+                        let progress = choices.iter().any(|choice| {
+                            let succs = || choice.succs.iter().filter(|(_, _, p)| *p > 0.0);
+                            succs().all(|(_, next_id, _)| stay[*next_id])
+                                && succs().any(|(_, next_id, _)| reached[*next_id])
+                        });
+                        if progress {
+                            reached[state_id] = true;
+                            changed = true;
+                        }
+                    }
+                    if !changed {
+                        break;
+                    }
+                }
+                if reached == stay {
+                    break stay;
+                }
+                stay = reached;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::tests::lts, process::Location};
+
+    #[test]
+    fn schedulers_optimize_reaching_b() {
+        // at 0, process 0 flips a coin between 1 and 2, process 1 moves to 3 surely,
+        // and process 2 waits; 1 and 3 are at `B`
+        let mut waiting = lts(vec![
+            vec![
+                ("coin", 1, Some(0)),
+                ("coin", 2, Some(0)),
+                ("sure", 3, Some(1)),
+                ("wait", 0, Some(2)),
+            ],
+            vec![],
+            vec![],
+            vec![],
+        ]);
+        let mdp = Mdp::new(&waiting).unwrap();
+        assert_eq!(mdp.0[0].len(), 3);
+        let at_b = || Formula::At(0, Location::new("B"));
+        let max = reach(&mut waiting, &mdp, |_, _| false, at_b(), Objective::Max).unwrap();
+        assert_eq!(max.probs, vec![1.0, 1.0, 0.0, 1.0]);
+        assert_eq!(max.scheduler[0], 1);
+        assert_eq!(max.trace(&mdp, 0).to_string(), "0 -sure-> 3");
+        let min = reach(&mut waiting, &mdp, |_, _| false, at_b(), Objective::Min).unwrap();
+        assert_eq!(min.probs, vec![0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(min.scheduler[0], 2);
+        assert_eq!(min.trace(&mdp, 0).to_string(), "0 -wait-> 0");
+        // without waiting, the coin is the worst choice
+        let mut no_wait = lts(vec![
+            vec![
+                ("coin", 1, Some(0)),
+                ("coin", 2, Some(0)),
+                ("sure", 3, Some(1)),
+            ],
+            vec![],
+            vec![],
+            vec![],
+        ]);
+        let mdp = Mdp::new(&no_wait).unwrap();
+        let min = reach(&mut no_wait, &mdp, |_, _| false, at_b(), Objective::Min).unwrap();
+        assert_eq!(min.probs[0], 0.5);
+        assert_eq!(min.scheduler[0], 0);
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{
+    formula::{Actions, Formula, Prop},
+    lts::Lts,
+    mark,
+};
+
+pub type Var = String;
+
+/// A modal mu-calculus formula.
+///
+/// for example "`p` holds on every even step" is `Nu("X", And(p, Square(Any, Square(Any, X))))`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum MuFormula {
+    True,
+    False,
+    /// a state formula, marked by `mark::make_marks_with`.
+    Atom(Formula),
+    Var(Var),
+    Not(Box<MuFormula>),
+    And(Box<MuFormula>, Box<MuFormula>),
+    Or(Box<MuFormula>, Box<MuFormula>),
+    /// `<a> f`: some `a`-successor satisfies `f`.
+    Diamond(Actions, Box<MuFormula>),
+    /// `[a] f`: every `a`-successor satisfies `f`.
+    Square(Actions, Box<MuFormula>),
+    /// least fixpoint.
+    Mu(Var, Box<MuFormula>),
+    /// greatest fixpoint.
+    Nu(Var, Box<MuFormula>),
+}
+
+impl MuFormula {
+    /// translates a CTL formula, preserving its meaning under `Deadlock::Finite`.
+    pub fn from_ctl(f: &Formula) -> Result<MuFormula, String> {
+        fn var(counter: &mut usize) -> Var {
+            *counter += 1;
+            format!("X{}", counter)
+        }
+        fn from_ctl_impl(f: &Formula, counter: &mut usize) -> Result<MuFormula, String> {
+            use MuFormula::*;
+            let b = Box::new;
+            // a maximal path may stop at a terminal state
+            let can_stop = || Square(Actions::Any, b(False));
+            Ok(match f {
+                Formula::True => True,
+                Formula::False => False,
+                Formula::Prop(_) | Formula::At(_, _) | Formula::Deadlock | Formula::Enabled(_) => {
+                    Atom(f.clone())
+                }
+                Formula::Not(box f) => Not(b(from_ctl_impl(f, counter)?)),
+                Formula::And(box f, box g) => {
+                    And(b(from_ctl_impl(f, counter)?), b(from_ctl_impl(g, counter)?))
+                }
+                Formula::Or(box f, box g) => {
+                    Or(b(from_ctl_impl(f, counter)?), b(from_ctl_impl(g, counter)?))
+                }
+                Formula::Impl(box f, box g) => Or(
+                    b(Not(b(from_ctl_impl(f, counter)?))),
+                    b(from_ctl_impl(g, counter)?),
+                ),
+                Formula::EX(box f) => Diamond(Actions::Any, b(from_ctl_impl(f, counter)?)),
+                Formula::EU(box f, box g) => {
+                    let x = var(counter);
+                    let body = Or(
+                        b(from_ctl_impl(g, counter)?),
+                        b(And(
+                            b(from_ctl_impl(f, counter)?),
+                            b(Diamond(Actions::Any, b(Var(x.clone())))),
+                        )),
+                    );
+                    Mu(x, b(body))
+                }
+                Formula::EG(box f) => {
+                    let x = var(counter);
+                    let body = And(
+                        b(from_ctl_impl(f, counter)?),
+                        b(Or(
+                            b(Diamond(Actions::Any, b(Var(x.clone())))),
+                            b(can_stop()),
+                        )),
+                    );
+                    Nu(x, b(body))
+                }
+                Formula::Diamond(a, box f) => Diamond(a.clone(), b(from_ctl_impl(f, counter)?)),
+                Formula::EUa(box f, a, a2, box g) => {
+                    let x = var(counter);
+                    let body = And(
+                        b(from_ctl_impl(f, counter)?),
+                        b(Or(
+                            b(Diamond(a2.clone(), b(from_ctl_impl(g, counter)?))),
+                            b(Diamond(a.clone(), b(Var(x.clone())))),
+                        )),
+                    );
+                    Mu(x, b(body))
+                }
+                Formula::EGa(a, box f) => {
+                    let x = var(counter);
+                    let body = And(
+                        b(from_ctl_impl(f, counter)?),
+                        b(Or(
+                            b(Diamond(a.clone(), b(Var(x.clone())))),
+                            b(Square(a.clone(), b(False))),
+                        )),
+                    );
+                    Nu(x, b(body))
+                }
+                Formula::EY(_) | Formula::EO(_) | Formula::EH(_) | Formula::ES(_, _) => {
+                    return Err(format!("past-time operator in {}", f))
+                }
+                Formula::K(_, _) | Formula::EK(_, _) | Formula::CK(_, _) => {
+                    return Err(format!("epistemic operator in {}", f))
+                }
+                // bounded operators unroll into k nested modalities
+                Formula::EUb(k, box f, box g) => {
+                    let (f, g) = (from_ctl_impl(f, counter)?, from_ctl_impl(g, counter)?);
+                    (0..*k).fold(g.clone(), |acc, _| {
+                        Or(
+                            b(g.clone()),
+                            b(And(b(f.clone()), b(Diamond(Actions::Any, b(acc))))),
+                        )
+                    })
+                }
+                Formula::AUb(k, box f, box g) => {
+                    let (f, g) = (from_ctl_impl(f, counter)?, from_ctl_impl(g, counter)?);
+                    (0..*k).fold(g.clone(), |acc, _| {
+                        let step = And(
+                            b(Diamond(Actions::Any, b(True))),
+                            b(Square(Actions::Any, b(acc))),
+                        );
+                        Or(b(g.clone()), b(And(b(f.clone()), b(step))))
+                    })
+                }
+                Formula::EGb(k, box f) => {
+                    let f = from_ctl_impl(f, counter)?;
+                    (0..*k).fold(f.clone(), |acc, _| {
+                        let step = Or(b(Diamond(Actions::Any, b(acc))), b(can_stop()));
+                        And(b(f.clone()), b(step))
+                    })
+                }
+            })
+        }
+        from_ctl_impl(f, &mut 0)
+    }
+}
+
+/// Evaluates the formula on every state of the lts (Emerson-Lei).
+///
+/// the approximation of a fixpoint is reused when it is re-entered inside an enclosing
+/// fixpoint of the same kind, and restarted otherwise.
+pub fn eval<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    f: &MuFormula,
+) -> Result<Vec<bool>, String> {
+    let f = pnf(f, true, &mut vec![], &mut 0)?;
+
+    let mut atoms = HashMap::new();
+    for atom in collect_atoms(&f) {
+        let sat = mark::satisfying(lts, prop_valuate, atom.clone())?;
+        atoms.insert(atom, sat);
+    }
+
+    let mut evaluator = Evaluator {
+        lts,
+        atoms,
+        env: HashMap::new(),
+    };
+    Ok(evaluator.eval(&f, None))
+}
+
+/// pushes negations down to the atoms, checking that every variable occurs positively.
+/// bound variables are renamed apart; `binders` holds the new name of each enclosing
+/// fixpoint variable and the polarity it is bound with.
+fn pnf(
+    f: &MuFormula,
+    positive: bool,
+    binders: &mut Vec<(Var, Var, bool)>,
+    counter: &mut usize,
+) -> Result<MuFormula, String> {
+    use MuFormula::*;
+    let b = Box::new;
+    Ok(match f {
+        True if positive => True,
+        True => False,
+        False if positive => False,
+        False => True,
+        Atom(a) if positive => Atom(a.clone()),
+        Atom(a) => Not(b(Atom(a.clone()))),
+        Var(x) => match binders.iter().rev().find(|(y, _, _)| y == x) {
+            Some((_, renamed, polarity)) if *polarity == positive => Var(renamed.clone()),
+            Some(_) => return Err(format!("variable {} occurs negatively", x)),
+            None => return Err(format!("variable {} is not bound", x)),
+        },
+        Not(f) => pnf(f, !positive, binders, counter)?,
+        And(f, g) if positive => And(
+            b(pnf(f, true, binders, counter)?),
+            b(pnf(g, true, binders, counter)?),
+        ),
+        And(f, g) => Or(
+            b(pnf(f, false, binders, counter)?),
+            b(pnf(g, false, binders, counter)?),
+        ),
+        Or(f, g) if positive => Or(
+            b(pnf(f, true, binders, counter)?),
+            b(pnf(g, true, binders, counter)?),
+        ),
+        Or(f, g) => And(
+            b(pnf(f, false, binders, counter)?),
+            b(pnf(g, false, binders, counter)?),
+        ),
+        Diamond(a, f) if positive => Diamond(a.clone(), b(pnf(f, true, binders, counter)?)),
+        Diamond(a, f) => Square(a.clone(), b(pnf(f, false, binders, counter)?)),
+        Square(a, f) if positive => Square(a.clone(), b(pnf(f, true, binders, counter)?)),
+        Square(a, f) => Diamond(a.clone(), b(pnf(f, false, binders, counter)?)),
+        Mu(x, g) | Nu(x, g) => {
+            *counter += 1;
+            let renamed = format!("{}#{}", x, counter);
+            binders.push((x.clone(), renamed.clone(), positive));
+            let body = pnf(g, positive, binders, counter)?;
+            binders.pop();
+            // not (mu X. g) == nu X. not g[not X / X]
+            if matches!(f, Mu(_, _)) == positive {
+                Mu(renamed, b(body))
+            } else {
+                Nu(renamed, b(body))
+            }
+        }
+    })
+}
+
+fn collect_atoms(f: &MuFormula) -> Vec<Formula> {
+    fn collect_atoms_impl(f: &MuFormula, acc: &mut Vec<Formula>) {
+        use MuFormula::*;
+        match f {
+            True | False | Var(_) => {}
+            Atom(a) => {
+                if !acc.contains(a) {
+                    acc.push(a.clone())
+                }
+            }
+            Not(g) | Diamond(_, g) | Square(_, g) | Mu(_, g) | Nu(_, g) => {
+                collect_atoms_impl(g, acc)
+            }
+            And(g, h) | Or(g, h) => {
+                collect_atoms_impl(g, acc);
+                collect_atoms_impl(h, acc);
+            }
+        }
+    }
+    let mut acc = vec![];
+    collect_atoms_impl(f, &mut acc);
+    acc
+}
+
+struct Evaluator<'a, T> {
+    lts: &'a Lts<T>,
+    atoms: HashMap<Formula, Vec<bool>>,
+    /// current approximation of each fixpoint variable.
+    env: HashMap<Var, Vec<bool>>,
+}
+
+impl<'a, T> Evaluator<'a, T> {
+    /// `parent` tells whether the innermost enclosing fixpoint is a least one.
+    fn eval(&mut self, f: &MuFormula, parent: Option<bool>) -> Vec<bool> {
+        use MuFormula::*;
+        let n = self.lts.0.len();
+        match f {
+            True => vec![true; n],
+            False => vec![false; n],
+            Atom(a) => self.atoms[a].clone(),
+            Not(box Atom(a)) => self.atoms[a].iter().map(|b| !b).collect(),
+            Not(_) => unreachable!("not in positive normal form"),
+            Var(x) => self.env[x].clone(),
+            And(g, h) => {
+                let lhs = self.eval(g, parent);
+                let rhs = self.eval(h, parent);
+                lhs.into_iter().zip(rhs).map(|(l, r)| l && r).collect()
+            }
+            Or(g, h) => {
+                let lhs = self.eval(g, parent);
+                let rhs = self.eval(h, parent);
+                lhs.into_iter().zip(rhs).map(|(l, r)| l || r).collect()
+            }
+            Diamond(a, g) => {
+                let sat = self.eval(g, parent);
+                self.lts
+                    .0
+                    .iter()
+                    .map(|trans| {
+                        trans
+                            .steps
+                            .iter()
+                            .any(|step| a.contains(&step.label) && sat[step.dst])
+                    })
+                    .collect()
+            }
+            Square(a, g) => {
+                let sat = self.eval(g, parent);
+                self.lts
+                    .0
+                    .iter()
+                    .map(|trans| {
+                        trans
+                            .steps
+                            .iter()
+                            .all(|step| !a.contains(&step.label) || sat[step.dst])
+                    })
+                    .collect()
+            }
+            Mu(x, g) | Nu(x, g) => {
+                let least = matches!(f, Mu(_, _));
+                if !self.env.contains_key(x) || parent != Some(least) {
+                    self.env.insert(x.clone(), vec![!least; n]);
+                    self.forget_inner(g);
+                }
+                loop {
+                    let next = self.eval(g, Some(least));
+                    if next == self.env[x] {
+                        break next;
+                    }
+                    self.env.insert(x.clone(), next);
+                }
+            }
+        }
+    }
+
+    /// drops the approximations of the fixpoints nested in `f`, so that they restart.
+    fn forget_inner(&mut self, f: &MuFormula) {
+        use MuFormula::*;
+        match f {
+            True | False | Atom(_) | Var(_) => {}
+            Not(g) | Diamond(_, g) | Square(_, g) => self.forget_inner(g),
+            And(g, h) | Or(g, h) => {
+                self.forget_inner(g);
+                self.forget_inner(h);
+            }
+            Mu(x, g) | Nu(x, g) => {
+                self.env.remove(x);
+                self.forget_inner(g);
+            }
+        }
+    }
+}
+
+use std::fmt;
+impl fmt::Display for MuFormula {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use MuFormula::*;
+        match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
+            Atom(ref a) => write!(fmt, "{}", a),
+            Var(ref x) => write!(fmt, "{}", x),
+            Not(ref f) => write!(fmt, "(not {})", f),
+            And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
+            Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
+            Diamond(ref a, ref f) => write!(fmt, "(<{}> {})", a, f),
+            Square(ref a, ref f) => write!(fmt, "([{}] {})", a, f),
+            Mu(ref x, ref f) => write!(fmt, "(mu {}. {})", x, f),
+            Nu(ref x, ref f) => write!(fmt, "(nu {}. {})", x, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lts::tests::lts,
+        process::{Label, Location},
+    };
+
+    /// `0 -a-> 1 -b-> 2 -c-> 3 -d-> 0`, at `A` in the even states.
+    fn cycle() -> Lts<i32> {
+        lts(vec![
+            vec![("a", 1, Some(0))],
+            vec![("b", 2, Some(0))],
+            vec![("c", 3, Some(0))],
+            vec![("d", 0, Some(0))],
+        ])
+    }
+
+    #[test]
+    fn greatest_fixpoint_every_even_step() {
+        use MuFormula::*;
+        let b = Box::new;
+        let at_a = Atom(Formula::At(0, Location::new("A")));
+        let every_even = Nu(
+            "X".to_string(),
+            b(And(
+                b(at_a),
+                b(Square(
+                    Actions::Any,
+                    b(Square(Actions::Any, b(Var("X".to_string())))),
+                )),
+            )),
+        );
+        assert_eq!(
+            eval(&mut cycle(), |_, _| false, &every_even),
+            Ok(vec![true, false, true, false])
+        );
+    }
+
+    #[test]
+    fn from_ctl_agrees_with_marking() {
+        let at = |location| Box::new(Formula::At(0, Location::new(location)));
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("b", 2, Some(0))],
+            vec![],
+            vec![("c", 2, Some(0))],
+        ]);
+        for f in [
+            Formula::EG(at("A")),
+            Formula::EU(at("A"), at("B")),
+            Formula::Not(Box::new(Formula::EU(
+                Box::new(Formula::True),
+                Box::new(Formula::Not(at("A"))),
+            ))),
+            Formula::EGa(Actions::Of(vec![Label::new("a")]), Box::new(Formula::True)),
+        ] {
+            let mu = MuFormula::from_ctl(&f).unwrap();
+            assert_eq!(
+                eval(&mut lts, |_, _| false, &mu),
+                mark::satisfying(&mut lts, |_, _| false, f)
+            );
+        }
+    }
+
+    #[test]
+    fn variables_must_occur_positively() {
+        use MuFormula::*;
+        let negative = Mu(
+            "X".to_string(),
+            Box::new(Not(Box::new(Var("X".to_string())))),
+        );
+        assert_eq!(
+            eval(&mut cycle(), |_, _| false, &negative),
+            Err("variable X occurs negatively".to_string())
+        );
+    }
+}
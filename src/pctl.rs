@@ -0,0 +1,323 @@
+use std::hash::Hash;
+
+use crate::{
+    dtmc::{self, Dtmc},
+    formula::{Formula, Prop},
+    graph,
+    lts::Lts,
+    mark,
+};
+
+/// tolerance of the probability bounds against rounding errors of the numerical methods.
+const EPSILON: f64 = 1e-9;
+
+/// A bound on a probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Ge(f64),
+    Gt(f64),
+    Le(f64),
+    Lt(f64),
+}
+
+impl Bound {
+    pub fn holds(&self, p: f64) -> bool {
+        match *self {
+            Bound::Ge(q) => p >= q - EPSILON,
+            Bound::Gt(q) => p > q + EPSILON,
+            Bound::Le(q) => p <= q + EPSILON,
+            Bound::Lt(q) => p < q - EPSILON,
+        }
+    }
+}
+
+/// A PCTL state formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pctl {
+    True,
+    False,
+    /// a CTL formula, marked by `mark::make_marks_with`.
+    Atom(Formula),
+    Not(Box<Pctl>),
+    And(Box<Pctl>, Box<Pctl>),
+    Or(Box<Pctl>, Box<Pctl>),
+    Impl(Box<Pctl>, Box<Pctl>),
+    /// the probability of the paths from the state satisfying the path formula is within the bound.
+    P(Bound, Box<PathFormula>),
+}
+
+/// A PCTL path formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathFormula {
+    X(Pctl),
+    U(Pctl, Pctl),
+    /// `f` until `g`, within `k` steps.
+    Ub(usize, Pctl, Pctl),
+    F(Pctl),
+    G(Pctl),
+}
+
+/// Evaluates `spec` on every state of the lts, with the probabilities of the dtmc built from it.
+pub fn check<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    dtmc: &Dtmc,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    spec: &Pctl,
+) -> Result<Vec<bool>, String> {
+    use Pctl::*;
+    let binary = |lhs: Vec<bool>, rhs: Vec<bool>, op: fn(bool, bool) -> bool| -> Vec<bool> {
+        lhs.into_iter().zip(rhs).map(|(l, r)| op(l, r)).collect()
+    };
+    Ok(match spec {
+        True => vec![true; lts.0.len()],
+        False => vec![false; lts.0.len()],
+        Atom(f) => mark::satisfying(lts, prop_valuate, f.clone())?,
+        Not(f) => check(lts, dtmc, prop_valuate, f)?
+            .into_iter()
+            .map(|b| !b)
+            .collect(),
+        And(f, g) => binary(
+            check(lts, dtmc, prop_valuate, f)?,
+            check(lts, dtmc, prop_valuate, g)?,
+            |l, r| l && r,
+        ),
+        Or(f, g) => binary(
+            check(lts, dtmc, prop_valuate, f)?,
+            check(lts, dtmc, prop_valuate, g)?,
+            |l, r| l || r,
+        ),
+        Impl(f, g) => binary(
+            check(lts, dtmc, prop_valuate, f)?,
+            check(lts, dtmc, prop_valuate, g)?,
+            |l, r| !l || r,
+        ),
+        P(bound, path) => probabilities(lts, dtmc, prop_valuate, path)?
+            .into_iter()
+            .map(|p| bound.holds(p))
+            .collect(),
+    })
+}
+
+/// The probability of the paths from each state satisfying `path`: the `P=?` query.
+pub fn probabilities<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    dtmc: &Dtmc,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    path: &PathFormula,
+) -> Result<Vec<f64>, String> {
+    use PathFormula::*;
+    if dtmc.0.len() != lts.0.len() {
+        return Err("the dtmc was not built from this lts".to_string());
+    }
+    Ok(match path {
+        X(f) => {
+            let sat = check(lts, dtmc, prop_valuate, f)?;
+            dtmc.0
+                .iter()
+                .map(|succs| {
+                    succs
+                        .iter()
+                        .filter(|(_, id, _)| sat[*id])
+                        .fold(0.0, |acc, (_, _, p)| acc + p)
+                })
+                .collect()
+        }
+        U(f, g) => {
+            let sat_f = check(lts, dtmc, prop_valuate, f)?;
+            let sat_g = check(lts, dtmc, prop_valuate, g)?;
+            until(dtmc, &sat_f, &sat_g)
+        }
+        Ub(k, f, g) => {
+            let sat_f = check(lts, dtmc, prop_valuate, f)?;
+            let sat_g = check(lts, dtmc, prop_valuate, g)?;
+            let mut probs: Vec<f64> = sat_g.iter().map(|g| if *g { 1.0 } else { 0.0 }).collect();
+            for _ in 0..*k {
+                probs = (0..dtmc.0.len())
+                    .map(|id| {
+                        if sat_g[id] {
+                            1.0
+                        } else if sat_f[id] {
+                            dtmc.0[id]
+                                .iter()
+                                .map(|(_, next_id, p)| p * probs[*next_id])
+                                .sum()
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+            }
+            probs
+        }
+        F(f) => probabilities(lts, dtmc, prop_valuate, &U(Pctl::True, f.clone()))?,
+        G(f) => {
+            let eventually_not = F(Pctl::Not(Box::new(f.clone())));
+            probabilities(lts, dtmc, prop_valuate, &eventually_not)?
+                .into_iter()
+                .map(|p| 1.0 - p)
+                .collect()
+        }
+    })
+}
+
+/// the probability of `f U g` from each state.
+///
+/// the states where it is 0 or 1 are found on the graph of the chain,
+/// the others by solving the linear equation system over them.
+pub(crate) fn until(dtmc: &Dtmc, sat_f: &[bool], sat_g: &[bool]) -> Vec<f64> {
+    let n = dtmc.0.len();
+    let (can_reach, can_fail) = qualitative(dtmc, sat_f, sat_g);
+
+    let unknown: Vec<usize> = (0..n)
+        .filter(|id| can_reach[*id] && can_fail[*id])
+        .collect();
+    let mut index = vec![None; n];
+    for (i, state_id) in unknown.iter().enumerate() {
+        index[*state_id] = Some(i);
+    }
+    let mut a = vec![vec![0.0; unknown.len()]; unknown.len()];
+    let mut b = vec![0.0; unknown.len()];
+    for (i, state_id) in unknown.iter().enumerate() {
+        a[i][i] += 1.0;
+        for (_, next_id, p) in dtmc.0[*state_id].iter() {
+            match index[*next_id] {
+                Some(j) => a[i][j] -= p,
+                None if !can_fail[*next_id] => b[i] += p,
+                None => {}
+            }
+        }
+    }
+    let x = dtmc::solve(a, b);
+
+    (0..n)
+        .map(|id| match index[id] {
+            Some(i) => x[i],
+            None if can_fail[id] => 0.0,
+            None => 1.0,
+        })
+        .collect()
+}
+
+/// the states where `f U g` holds with probability 1, found on the graph of the chain.
+pub(crate) fn almost_sure(dtmc: &Dtmc, sat_f: &[bool], sat_g: &[bool]) -> Vec<bool> {
+    let (_, can_fail) = qualitative(dtmc, sat_f, sat_g);
+    can_fail.into_iter().map(|b| !b).collect()
+}
+
+/// the states from which `f U g` holds, and those from which it fails, with a positive
+/// probability.
+fn qualitative(dtmc: &Dtmc, sat_f: &[bool], sat_g: &[bool]) -> (Vec<bool>, Vec<bool>) {
+    let n = dtmc.0.len();
+    let preds = dtmc.predecessors();
+    let targets: Vec<usize> = (0..n).filter(|id| sat_g[*id]).collect();
+    let can_reach = graph::backward_reach(n, &targets, |v| preds[v].clone(), |v| sat_f[v]);
+    let never: Vec<usize> = (0..n).filter(|id| !can_reach[*id]).collect();
+    let can_fail =
+        graph::backward_reach(n, &never, |v| preds[v].clone(), |v| sat_f[v] && !sat_g[v]);
+    (can_reach, can_fail)
+}
+
+use std::fmt;
+impl fmt::Display for Bound {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bound::Ge(ref p) => write!(fmt, ">={}", p),
+            Bound::Gt(ref p) => write!(fmt, ">{}", p),
+            Bound::Le(ref p) => write!(fmt, "<={}", p),
+            Bound::Lt(ref p) => write!(fmt, "<{}", p),
+        }
+    }
+}
+
+impl fmt::Display for Pctl {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Pctl::*;
+        match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
+            Atom(ref f) => write!(fmt, "{}", f),
+            Not(ref f) => write!(fmt, "(not {})", f),
+            And(ref lhs, ref rhs) => write!(fmt, "(and {} {})", lhs, rhs),
+            Or(ref lhs, ref rhs) => write!(fmt, "(or {} {})", lhs, rhs),
+            Impl(ref lhs, ref rhs) => write!(fmt, "(impl {} {})", lhs, rhs),
+            P(ref bound, ref path) => write!(fmt, "(P{} {})", bound, path),
+        }
+    }
+}
+
+impl fmt::Display for PathFormula {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use PathFormula::*;
+        match self {
+            X(ref f) => write!(fmt, "(X {})", f),
+            U(ref f, ref g) => write!(fmt, "(U {} {})", f, g),
+            Ub(ref k, ref f, ref g) => write!(fmt, "(U<={} {} {})", k, f, g),
+            F(ref f) => write!(fmt, "(F {})", f),
+            G(ref f) => write!(fmt, "(G {})", f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dtmc::Scheduler,
+        lts::{tests::lts, Lts},
+        process::Location,
+    };
+
+    /// 0 retries, succeeds into 1 or fails into 2 with equal probabilities. 1 and 2 loop
+    /// on themselves in the dtmc.
+    fn retry() -> Lts<i32> {
+        lts(vec![
+            vec![
+                ("retry", 0, Some(0)),
+                ("ok", 1, Some(0)),
+                ("fail", 2, Some(0)),
+            ],
+            vec![],
+            vec![],
+        ])
+    }
+
+    fn assert_close(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (p, q) in actual.iter().zip(expected) {
+            assert!((p - q).abs() < 1e-6, "{:?} is not {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn probabilities_of_path_formulas() {
+        let mut lts = retry();
+        let dtmc = Dtmc::new(&lts, &Scheduler::Uniform).unwrap();
+        let ok = || Pctl::Atom(Formula::At(0, Location::new("B")));
+        let mut p = |path| probabilities(&mut lts, &dtmc, |_, _| false, &path).unwrap();
+        assert_close(p(PathFormula::X(ok())), &[1.0 / 3.0, 1.0, 0.0]);
+        assert_close(
+            p(PathFormula::Ub(2, Pctl::True, ok())),
+            &[4.0 / 9.0, 1.0, 0.0],
+        );
+        assert_close(p(PathFormula::F(ok())), &[0.5, 1.0, 0.0]);
+        assert_close(
+            p(PathFormula::G(Pctl::Not(Box::new(ok())))),
+            &[0.5, 0.0, 1.0],
+        );
+        let likely = Pctl::P(Bound::Ge(0.5), Box::new(PathFormula::F(ok())));
+        assert_eq!(
+            check(&mut lts, &dtmc, |_, _| false, &likely),
+            Ok(vec![true, true, false])
+        );
+    }
+
+    #[test]
+    fn fixed_scheduler_must_pick_an_enabled_process() {
+        let lts = retry();
+        let result = Dtmc::new(&lts, &Scheduler::Fixed(|_, _| 1));
+        assert_eq!(
+            result.unwrap_err(),
+            "scheduler picked process 1 not enabled at state 0"
+        );
+    }
+}
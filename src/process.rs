@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::timed::{Clock, ClockConstraint};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Location(pub String);
 
@@ -15,7 +17,7 @@ impl fmt::Display for Location {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Label(pub String);
 
 impl Label {
@@ -39,10 +41,16 @@ pub struct Trans<T> {
     pub dst: Location,
     pub guard: Guard<T>,
     pub action: Action<T>,
+    /// the constraints the clocks must also satisfy, only read by `timed::zone_graph`.
+    pub clock_guard: Vec<ClockConstraint>,
+    /// the clocks set to 0 by the transition.
+    pub resets: Vec<Clock>,
 }
 
 pub struct ExecUnit<T> {
     pub src: Location,
+    /// the constraints the clocks must satisfy to stay at the location.
+    pub invariant: Vec<ClockConstraint>,
     pub transs: Vec<Trans<T>>,
 }
 
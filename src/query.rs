@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use crate::{
+    graph,
+    lts::{Lts, StateId},
+    mark::Marks,
+};
+
+/// An integer attached to each state, `None` where the query has no finite answer.
+/// `viz::lts_annotated` prints it next to the marks.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub name: String,
+    pub values: Vec<Option<usize>>,
+}
+
+// the queries below take as target the states where the marked specification holds.
+
+fn targets<T: Clone + Hash + Eq>(lts: &Lts<T>, marks: &Marks) -> Vec<StateId> {
+    lts.find_states(|state_id, _| marks.holds_at(state_id))
+}
+
+/// the least number of steps from each state to a target.
+pub fn min_distance<T: Clone + Hash + Eq>(lts: &Lts<T>, marks: &Marks) -> Annotation {
+    let preds = lts.predecessors();
+    let mut values = vec![None; lts.0.len()];
+    let mut queue = VecDeque::new();
+    for state_id in targets(lts, marks) {
+        values[state_id] = Some(0);
+        queue.push_back(state_id);
+    }
+    while let Some(state_id) = queue.pop_front() {
+        let d = values[state_id].unwrap();
+        for pred_id in preds[state_id].iter() {
+            if values[*pred_id].is_none() {
+                values[*pred_id] = Some(d + 1);
+                queue.push_back(*pred_id);
+            }
+        }
+    }
+    Annotation {
+        name: "min".to_string(),
+        values,
+    }
+}
+
+/// the greatest number of steps a path from each state can take before it first
+/// reaches a target, among the paths which do reach one.
+/// `None` if no target is reachable, or if such paths can be arbitrarily long.
+pub fn max_distance<T: Clone + Hash + Eq>(lts: &Lts<T>, marks: &Marks) -> Annotation {
+    let n = lts.0.len();
+    let preds = lts.predecessors();
+    let is_target: Vec<bool> = (0..n).map(|id| marks.holds_at(id)).collect();
+    let can_reach = graph::backward_reach(n, &targets(lts, marks), |v| preds[v].clone(), |_| true);
+    // states still on their way to a target
+    let on_way: Vec<bool> = (0..n).map(|id| can_reach[id] && !is_target[id]).collect();
+    let succs = |v: StateId| -> Vec<StateId> {
+        lts.0[v]
+            .steps
+            .iter()
+            .map(|step| step.dst)
+            .filter(|next_id| on_way[*next_id])
+            .collect()
+    };
+
+    let on_way_ids: Vec<StateId> = (0..n).filter(|id| on_way[*id]).collect();
+    let components = graph::sccs(n, &on_way_ids, succs);
+    let cyclic: Vec<StateId> = components
+        .iter()
+        .filter(|component| graph::is_nontrivial(component, succs))
+        .flatten()
+        .cloned()
+        .collect();
+    let unbounded = graph::backward_reach(n, &cyclic, |v| preds[v].clone(), |v| on_way[v]);
+
+    let mut values: Vec<Option<usize>> = (0..n)
+        .map(|id| if is_target[id] { Some(0) } else { None })
+        .collect();
+    // the components are single states here, and come successors first
+    for component in components {
+        let state_id = component[0];
+        if unbounded[state_id] {
+            continue;
+        }
+        values[state_id] = lts.0[state_id]
+            .steps
+            .iter()
+            .filter_map(|step| values[step.dst])
+            .max()
+            .map(|d| d + 1);
+    }
+    Annotation {
+        name: "max".to_string(),
+        values,
+    }
+}
+
+/// the number of targets reachable from each state, the state itself included.
+pub fn count_reachable<T>(lts: &Lts<T>, marks: &Marks) -> Annotation {
+    let n = lts.0.len();
+    let values = (0..n)
+        .map(|state_id| {
+            let mut reached = vec![false; n];
+            reached[state_id] = true;
+            let mut stack = vec![state_id];
+            while let Some(id) = stack.pop() {
+                for step in lts.0[id].steps.iter() {
+                    if !reached[step.dst] {
+                        reached[step.dst] = true;
+                        stack.push(step.dst);
+                    }
+                }
+            }
+            Some(
+                (0..n)
+                    .filter(|id| reached[*id] && marks.holds_at(*id))
+                    .count(),
+            )
+        })
+        .collect();
+    Annotation {
+        name: "count".to_string(),
+        values,
+    }
+}
+
+/// whether a target is reachable from each state in at most `k` steps.
+pub fn reachable_within<T: Clone + Hash + Eq>(lts: &Lts<T>, marks: &Marks, k: usize) -> Vec<bool> {
+    min_distance(lts, marks)
+        .values
+        .into_iter()
+        .map(|d| d.is_some_and(|d| d <= k))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{formula::Formula, lts::tests::lts, mark};
+
+    #[test]
+    fn distances_to_the_deadlock() {
+        // 0 -a-> 1 -b-> 2 -c-> 3 and 0 -d-> 3, 4 may loop before 3, 5 only loops
+        let mut lts = lts(vec![
+            vec![("a", 1, Some(0)), ("d", 3, Some(0))],
+            vec![("b", 2, Some(0))],
+            vec![("c", 3, Some(0))],
+            vec![],
+            vec![("f", 4, Some(0)), ("g", 3, Some(0))],
+            vec![("h", 5, Some(0))],
+        ]);
+        let marks = mark::make_marks(&mut lts, |_, _| false, Formula::Deadlock);
+        let min = min_distance(&lts, &marks);
+        assert_eq!(min.name, "min");
+        assert_eq!(
+            min.values,
+            vec![Some(1), Some(2), Some(1), Some(0), Some(1), None]
+        );
+        assert_eq!(
+            max_distance(&lts, &marks).values,
+            vec![Some(3), Some(2), Some(1), Some(0), None, None]
+        );
+        assert_eq!(
+            count_reachable(&lts, &marks).values,
+            vec![Some(1), Some(1), Some(1), Some(1), Some(1), Some(0)]
+        );
+        assert_eq!(
+            reachable_within(&lts, &marks, 1),
+            vec![true, false, true, true, true, false]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::{
+    lts::{Lts, StateId, Trans, TAU},
+    process::Label,
+};
+
+/// What the implementation must not do beyond the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    /// every trace of the implementation is a trace of the specification.
+    Traces,
+    /// moreover, after such a trace, every set of labels a stable state of the implementation
+    /// refuses is refused by a stable state of the specification (stable failures).
+    Failures,
+    /// moreover, the implementation may only diverge, i.e. take `TAU` transitions forever,
+    /// after a trace the specification diverges on, after which anything is allowed.
+    FailuresDivergences,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// a trace of the implementation which the specification cannot perform.
+    Trace(Vec<Label>),
+    /// after the trace, the implementation can reach a stable state offering only the
+    /// labels `accepted`, while the specification cannot refuse all the others.
+    Failure {
+        trace: Vec<Label>,
+        accepted: Vec<Label>,
+    },
+    /// after the trace, the implementation can diverge while the specification cannot.
+    Divergence(Vec<Label>),
+}
+
+/// Checks that the implementation refines the specification, e.g. a process given as
+/// `Lts::concurrent_composition` of itself alone, and returns a violation with a shortest
+/// trace otherwise.
+///
+/// `TAU` transitions are internal and `STUTTER` loops are ignored. the specification is
+/// normalized by a subset construction over its `TAU` closures, which is searched in
+/// product with the implementation, breadth first by the number of visible steps.
+pub fn check<S, I>(
+    spec: &Lts<S>,
+    implementation: &Lts<I>,
+    semantics: Semantics,
+) -> Option<Violation> {
+    let mut normal = Normal {
+        lts: spec,
+        nodes: vec![],
+        ids: HashMap::new(),
+        after: HashMap::new(),
+    };
+    let init = normal.node(closure(spec, std::iter::once(0).collect()));
+    let divergences = semantics == Semantics::FailuresDivergences;
+    let (spec_divergent, impl_divergent) = if divergences {
+        (divergent(spec), divergent(implementation))
+    } else {
+        (vec![], vec![])
+    };
+    let start = (0, init);
+    // 0-1 breadth first by the number of visible steps: `TAU` steps go to the front, and
+    // a pair is popped again if a `TAU` step finds a shorter route to it
+    let mut parent: HashMap<Pair, Option<(Pair, Option<Label>)>> = HashMap::new();
+    let mut distance: HashMap<Pair, usize> = HashMap::new();
+    parent.insert(start, None);
+    distance.insert(start, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+    let trace_to = |parent: &HashMap<_, Option<(_, Option<Label>)>>, pair| {
+        let mut trace = vec![];
+        let mut pair = pair;
+        while let Some((prev, label)) = parent[&pair].clone() {
+            trace.extend(label);
+            pair = prev;
+        }
+        trace.reverse();
+        trace
+    };
+    // a trace violation one step longer than the pair found it, kept until no shorter
+    // violation can turn up
+    let mut found: Option<Vec<Label>> = None;
+    while let Some(((state_id, node), d)) = queue.pop_front() {
+        if found.as_ref().is_some_and(|trace| trace.len() <= d) {
+            break;
+        }
+        if distance[&(state_id, node)] < d {
+            continue;
+        }
+        if divergences && normal.nodes[node].iter().any(|id| spec_divergent[*id]) {
+            continue;
+        }
+        if divergences && impl_divergent[state_id] {
+            return Some(Violation::Divergence(trace_to(&parent, (state_id, node))));
+        }
+        let trans = &implementation.0[state_id];
+        if semantics != Semantics::Traces && is_stable(trans) {
+            let accepted = initials(trans);
+            let refusable = normal.nodes[node].iter().any(|spec_id| {
+                let spec_trans = &spec.0[*spec_id];
+                is_stable(spec_trans) && initials(spec_trans).is_subset(&accepted)
+            });
+            if !refusable {
+                return Some(Violation::Failure {
+                    trace: trace_to(&parent, (state_id, node)),
+                    accepted: accepted.into_iter().map(Label::new).collect(),
+                });
+            }
+        }
+        for step in trans.steps.iter() {
+            let (label, next_id) = (&step.label, &step.dst);
+            if step.process.is_none() {
+                continue;
+            }
+            let (next, weight, label) = if label.0 == TAU {
+                ((*next_id, node), 0, None)
+            } else {
+                match normal.after(node, label) {
+                    Some(next_node) => ((*next_id, next_node), 1, Some(label.clone())),
+                    None => {
+                        if found.is_none() {
+                            let mut trace = trace_to(&parent, (state_id, node));
+                            trace.push(label.clone());
+                            found = Some(trace);
+                        }
+                        continue;
+                    }
+                }
+            };
+            if distance.get(&next).is_none_or(|known| d + weight < *known) {
+                distance.insert(next, d + weight);
+                parent.insert(next, Some(((state_id, node), label)));
+                if weight == 0 {
+                    queue.push_front((next, d));
+                } else {
+                    queue.push_back((next, d + 1));
+                }
+            }
+        }
+    }
+    found.map(Violation::Trace)
+}
+
+/// a state of the implementation with a node of the normalized specification.
+type Pair = (StateId, usize);
+
+/// The normalized specification: its nodes are the sets of states it may be in after a trace.
+struct Normal<'a, S> {
+    lts: &'a Lts<S>,
+    nodes: Vec<BTreeSet<StateId>>,
+    ids: HashMap<BTreeSet<StateId>, usize>,
+    after: HashMap<(usize, Label), Option<usize>>,
+}
+
+impl<'a, S> Normal<'a, S> {
+    fn node(&mut self, states: BTreeSet<StateId>) -> usize {
+        if let Some(id) = self.ids.get(&states) {
+            return *id;
+        }
+        self.nodes.push(states.clone());
+        self.ids.insert(states, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// the node after the visible label, if the specification can perform it.
+    fn after(&mut self, node: usize, label: &Label) -> Option<usize> {
+        let key = (node, label.clone());
+        if let Some(next) = self.after.get(&key) {
+            return *next;
+        }
+        let successors: BTreeSet<StateId> = self.nodes[node]
+            .iter()
+            .flat_map(|state_id| self.lts.0[*state_id].steps.iter())
+            .filter(|step| &step.label == label)
+            .map(|step| step.dst)
+            .collect();
+        let next = if successors.is_empty() {
+            None
+        } else {
+            Some(self.node(closure(self.lts, successors)))
+        };
+        self.after.insert(key, next);
+        next
+    }
+}
+
+/// the states reachable by `TAU` transitions.
+fn closure<T>(lts: &Lts<T>, states: BTreeSet<StateId>) -> BTreeSet<StateId> {
+    let mut closure = states.clone();
+    let mut stack: Vec<StateId> = states.into_iter().collect();
+    while let Some(state_id) = stack.pop() {
+        for step in lts.0[state_id].steps.iter() {
+            if step.label.0 == TAU && closure.insert(step.dst) {
+                stack.push(step.dst);
+            }
+        }
+    }
+    closure
+}
+
+/// the states which can take `TAU` transitions forever: the greatest set of states with a
+/// `TAU` transition into the set.
+fn divergent<T>(lts: &Lts<T>) -> Vec<bool> {
+    let mut divergent = vec![true; lts.0.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (state_id, trans) in lts.0.iter().enumerate() {
+            if divergent[state_id]
+                && !trans.steps.iter().any(|step| {
+                    step.process.is_some() && step.label.0 == TAU && divergent[step.dst]
+                })
+            {
+                divergent[state_id] = false;
+                changed = true;
+            }
+        }
+    }
+    divergent
+}
+
+/// a state is stable if it has no internal transition.
+fn is_stable<T>(trans: &Trans<T>) -> bool {
+    trans.steps.iter().all(|step| step.label.0 != TAU)
+}
+
+/// the visible labels of the transitions of the state.
+fn initials<T>(trans: &Trans<T>) -> BTreeSet<&str> {
+    trans
+        .steps
+        .iter()
+        .filter(|step| step.process.is_some())
+        .map(|step| step.label.0.as_str())
+        .filter(|label| *label != TAU)
+        .collect()
+}
+
+use std::fmt;
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let labels = |labels: &[Label]| {
+            labels
+                .iter()
+                .map(|label| label.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        match self {
+            Violation::Trace(trace) => write!(fmt, "trace <{}>", labels(trace)),
+            Violation::Failure { trace, accepted } => write!(
+                fmt,
+                "failure <{}> accepting only {{{}}}",
+                labels(trace),
+                labels(accepted)
+            ),
+            Violation::Divergence(trace) => write!(fmt, "divergence <{}>", labels(trace)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lts::tests::lts;
+
+    fn labels(labels: &[&str]) -> Vec<Label> {
+        labels.iter().map(|label| Label::new(label)).collect()
+    }
+
+    #[test]
+    fn trace_is_shortest_across_tau() {
+        // `b` is reached by `a` first, but also by two `TAU` steps
+        let spec = lts(vec![vec![("a", 0, Some(0))]]);
+        let implementation = lts(vec![
+            vec![("a", 1, Some(0)), (TAU, 2, Some(0))],
+            vec![("b", 3, Some(0))],
+            vec![(TAU, 1, Some(0))],
+            vec![],
+        ]);
+        assert_eq!(
+            check(&spec, &implementation, Semantics::Traces),
+            Some(Violation::Trace(labels(&["b"])))
+        );
+    }
+
+    #[test]
+    fn internal_choice_fails_external_choice() {
+        let spec = lts(vec![vec![("a", 1, Some(0)), ("b", 1, Some(0))], vec![]]);
+        let implementation = lts(vec![
+            vec![(TAU, 1, Some(0)), (TAU, 2, Some(0))],
+            vec![("a", 3, Some(0))],
+            vec![("b", 3, Some(0))],
+            vec![],
+        ]);
+        assert_eq!(check(&spec, &implementation, Semantics::Traces), None);
+        assert_eq!(
+            check(&spec, &implementation, Semantics::Failures),
+            Some(Violation::Failure {
+                trace: vec![],
+                accepted: labels(&["b"]),
+            })
+        );
+    }
+
+    #[test]
+    fn divergence_only_fails_failures_divergences() {
+        let spec = lts(vec![vec![("a", 0, Some(0))]]);
+        let implementation = lts(vec![vec![("a", 1, Some(0))], vec![(TAU, 1, Some(0))]]);
+        assert_eq!(check(&spec, &implementation, Semantics::Failures), None);
+        assert_eq!(
+            check(&spec, &implementation, Semantics::FailuresDivergences),
+            Some(Violation::Divergence(labels(&["a"])))
+        );
+    }
+
+    #[test]
+    fn divergent_spec_allows_anything() {
+        let spec = lts(vec![vec![("a", 1, Some(0))], vec![(TAU, 1, Some(0))]]);
+        let implementation = lts(vec![
+            vec![("a", 1, Some(0))],
+            vec![(TAU, 1, Some(0)), ("c", 1, Some(0))],
+        ]);
+        assert_eq!(
+            check(&spec, &implementation, Semantics::FailuresDivergences),
+            None
+        );
+        assert_eq!(
+            check(&spec, &implementation, Semantics::Traces),
+            Some(Violation::Trace(labels(&["a", "c"])))
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::hash::Hash;
+
+use crate::{
+    dtmc::{self, Dtmc},
+    formula::{Formula, Prop},
+    graph,
+    lts::{Lts, State, StateId},
+    mark,
+    mdp::{self, Mdp, Objective},
+    pctl,
+    process::Label,
+};
+
+/// bound on the relative change of the values at which value iteration stops.
+const EPSILON: f64 = 1e-12;
+
+const MAX_ITERATIONS: usize = 1_000_000;
+
+/// What is earned along a path. Rewards must not be negative.
+pub enum Reward<T> {
+    /// earned in each state left before reaching the target.
+    State(fn(&State<T>) -> f64),
+    /// earned on each transition taken, by its label.
+    Label(fn(&Label) -> f64),
+    /// one per transition taken: the expected number of steps.
+    Steps,
+}
+
+impl<T> Reward<T> {
+    /// the expected reward of leaving the state by the given transitions.
+    fn of_step(&self, state: &State<T>, succs: &[(Label, StateId, f64)]) -> Result<f64, String> {
+        let r = match self {
+            Reward::State(f) => f(state),
+            Reward::Label(f) => {
+                let mut r = 0.0;
+                for (label, _, p) in succs.iter() {
+                    let reward = f(label);
+                    if reward < 0.0 {
+                        return Err(format!("negative reward {} on {}", reward, label));
+                    }
+                    r += p * reward;
+                }
+                r
+            }
+            Reward::Steps => 1.0,
+        };
+        if r < 0.0 || r.is_nan() {
+            return Err(format!("reward {} is not a non-negative number", r));
+        }
+        Ok(r)
+    }
+}
+
+/// The expected reward accumulated from each state until reaching a state where `target`
+/// holds: the `R=? [F target]` query. It is infinite where the target is missed
+/// with a positive probability.
+pub fn expected_dtmc<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    dtmc: &Dtmc,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    reward: &Reward<T>,
+    target: Formula,
+) -> Result<Vec<f64>, String> {
+    if dtmc.0.len() != lts.0.len() {
+        return Err("the dtmc was not built from this lts".to_string());
+    }
+    let n = lts.0.len();
+    let sat = mark::satisfying(lts, prop_valuate, target)?;
+    let sure = pctl::almost_sure(dtmc, &vec![true; n], &sat);
+
+    let unknown: Vec<StateId> = (0..n).filter(|id| sure[*id] && !sat[*id]).collect();
+    let mut index = vec![None; n];
+    for (i, state_id) in unknown.iter().enumerate() {
+        index[*state_id] = Some(i);
+    }
+    let mut a = vec![vec![0.0; unknown.len()]; unknown.len()];
+    let mut b = vec![0.0; unknown.len()];
+    for (i, state_id) in unknown.iter().enumerate() {
+        let succs = &dtmc.0[*state_id];
+        a[i][i] += 1.0;
+        b[i] = reward.of_step(&lts.0[*state_id].state, succs)?;
+        for (_, next_id, p) in succs.iter() {
+            if let Some(j) = index[*next_id] {
+                a[i][j] -= p;
+            }
+        }
+    }
+    let x = dtmc::solve(a, b);
+
+    Ok((0..n)
+        .map(|id| match index[id] {
+            Some(i) => x[i],
+            None if sat[id] => 0.0,
+            None => f64::INFINITY,
+        })
+        .collect())
+}
+
+/// `Rmin` or `Rmax` of the reward accumulated until reaching a state where `target` holds,
+/// by value iteration. It is infinite where the scheduler can miss the target
+/// (`Max`) or cannot avoid missing it (`Min`) with a positive probability.
+/// under `Min` only the schedulers reaching the target almost surely count, so looping
+/// forever without earning anything does not: the end components of such loops outside
+/// the target are collapsed, and left by their best other choice.
+pub fn expected_mdp<T: Clone + Hash + Eq>(
+    lts: &mut Lts<T>,
+    mdp: &Mdp,
+    prop_valuate: fn(&Prop, &T) -> bool,
+    reward: &Reward<T>,
+    target: Formula,
+    objective: Objective,
+) -> Result<Vec<f64>, String> {
+    if mdp.0.len() != lts.0.len() {
+        return Err("the mdp was not built from this lts".to_string());
+    }
+    let n = lts.0.len();
+    let sat = mark::satisfying(lts, prop_valuate, target)?;
+    let dual = match objective {
+        Objective::Min => Objective::Max,
+        Objective::Max => Objective::Min,
+    };
+    let finite = mdp::almost_sure(mdp, &sat, dual);
+
+    // the choices keeping the reward finite, with their rewards
+    let mut choices = vec![vec![]; n];
+    for state_id in (0..n).filter(|id| finite[*id] && !sat[*id]) {
+        for choice in mdp.0[state_id].iter() {
+            if choice.succs.iter().all(|(_, next_id, _)| finite[*next_id]) {
+                let r = reward.of_step(&lts.0[state_id].state, &choice.succs)?;
+                choices[state_id].push((r, choice.succs.as_slice()));
+            }
+        }
+    }
+
+    // the states of each collapsed end component, or the state alone
+    let mut members: Vec<Vec<StateId>> = (0..n).map(|id| vec![id]).collect();
+    if objective == Objective::Min {
+        for component in zero_reward_components(&choices) {
+            for state_id in component.iter() {
+                let internal = |r: f64, succs: &Succs| {
+                    r == 0.0
+                        && succs
+                            .iter()
+                            .all(|(_, next_id, _)| component.contains(next_id))
+                };
+                choices[*state_id].retain(|(r, succs)| !internal(*r, succs));
+                members[*state_id] = component.clone();
+            }
+        }
+    }
+
+    let mut values: Vec<f64> = (0..n)
+        .map(|id| if finite[id] { 0.0 } else { f64::INFINITY })
+        .collect();
+    for _ in 0..MAX_ITERATIONS {
+        let mut change: f64 = 0.0;
+        for state_id in 0..n {
+            if members[state_id].iter().all(|id| choices[*id].is_empty()) {
+                continue;
+            }
+            let outcomes = members[state_id]
+                .iter()
+                .flat_map(|id| choices[*id].iter())
+                .map(|(r, succs)| {
+                    succs
+                        .iter()
+                        .fold(*r, |acc, (_, next_id, p)| acc + p * values[*next_id])
+                });
+            let v = match objective {
+                Objective::Min => outcomes.fold(f64::INFINITY, f64::min),
+                Objective::Max => outcomes.fold(0.0, f64::max),
+            };
+            change = change.max((v - values[state_id]).abs() / v.max(1.0));
+            values[state_id] = v;
+        }
+        if change < EPSILON {
+            break;
+        }
+    }
+    Ok(values)
+}
+
+/// the successors of a choice, with their probabilities.
+type Succs = [(Label, StateId, f64)];
+
+/// the maximal end components of the choices without reward: the sets of states which
+/// such choices can keep a path in forever, while visiting all of them.
+fn zero_reward_components(choices: &[Vec<(f64, &Succs)>]) -> Vec<Vec<StateId>> {
+    let n = choices.len();
+    let mut kept: Vec<Vec<&Succs>> = choices
+        .iter()
+        .map(|cs| {
+            cs.iter()
+                .filter(|(r, _)| *r == 0.0)
+                .map(|(_, s)| *s)
+                .collect()
+        })
+        .collect();
+    loop {
+        let succs = |v: StateId| -> Vec<StateId> {
+            kept[v]
+                .iter()
+                .flat_map(|succs| succs.iter().map(|(_, next_id, _)| *next_id))
+                .collect()
+        };
+        let roots: Vec<StateId> = (0..n).filter(|id| !kept[*id].is_empty()).collect();
+        let components = graph::sccs(n, &roots, succs);
+        let mut component_of = vec![None; n];
+        for (c, component) in components.iter().enumerate() {
+            for state_id in component.iter() {
+                component_of[*state_id] = Some(c);
+            }
+        }
+        // a choice which may leave the component of its state cannot stay in it forever
+        let mut changed = false;
+        for state_id in roots {
+            let before = kept[state_id].len();
+            kept[state_id].retain(|succs| {
+                succs
+                    .iter()
+                    .all(|(_, next_id, _)| component_of[*next_id] == component_of[state_id])
+            });
+            changed |= kept[state_id].len() != before;
+        }
+        if !changed {
+            return components
+                .into_iter()
+                .filter(|component| component.iter().all(|id| !kept[*id].is_empty()))
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtmc::Scheduler;
+    use crate::lts::tests::lts;
+
+    fn done(_: &Prop, x: &i32) -> bool {
+        *x == 3
+    }
+
+    fn target() -> Formula {
+        Formula::Prop("done".to_string())
+    }
+
+    #[test]
+    fn expected_steps_of_a_dtmc() {
+        // 0 loops or moves to 3 with probability 1/2, 1 cannot reach 3
+        let mut lts = lts(vec![
+            vec![("a", 0, Some(0)), ("b", 3, Some(0))],
+            vec![("a", 2, Some(0))],
+            vec![("a", 1, Some(0))],
+            vec![],
+        ]);
+        let dtmc = Dtmc::new(&lts, &Scheduler::Uniform).unwrap();
+        let steps = expected_dtmc(&mut lts, &dtmc, done, &Reward::Steps, target()).unwrap();
+        assert!((steps[0] - 2.0).abs() < 1e-9);
+        assert_eq!(steps[1], f64::INFINITY);
+        assert_eq!(steps[3], 0.0);
+    }
+
+    #[test]
+    fn rmin_does_not_loop_for_free() {
+        // 0 and 1 can pass the turn to each other forever for free, or pay to finish
+        let mut lts = lts(vec![
+            vec![("pass", 1, Some(0)), ("go5", 3, Some(1))],
+            vec![("pass", 0, Some(0)), ("go2", 3, Some(1))],
+            vec![("idle", 2, Some(0)), ("go5", 3, Some(1))],
+            vec![],
+        ]);
+        let mdp = Mdp::new(&lts).unwrap();
+        let reward = Reward::Label(|label| match label.0.as_str() {
+            "go5" => 5.0,
+            "go2" => 2.0,
+            _ => 0.0,
+        });
+        let min = expected_mdp(&mut lts, &mdp, done, &reward, target(), Objective::Min).unwrap();
+        assert_eq!(min, vec![2.0, 2.0, 5.0, 0.0]);
+        let max = expected_mdp(&mut lts, &mdp, done, &reward, target(), Objective::Max).unwrap();
+        assert_eq!(max[..3], [f64::INFINITY; 3]);
+    }
+}
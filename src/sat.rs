@@ -0,0 +1,414 @@
+use std::collections::BinaryHeap;
+use std::ops;
+
+pub type Var = usize;
+
+/// A variable or its negation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(usize);
+
+impl Lit {
+    pub fn new(var: Var, positive: bool) -> Lit {
+        Lit(2 * var + !positive as usize)
+    }
+
+    pub fn var(self) -> Var {
+        self.0 / 2
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+}
+
+impl ops::Not for Lit {
+    type Output = Lit;
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// A CDCL SAT solver: two watched literals, first-UIP clause learning, activity-based
+/// decisions with phase saving and Luby restarts.
+///
+/// clauses may be added between calls to `solve`, which can take assumptions.
+#[derive(Debug, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// the clauses watching each literal, to visit when it becomes false.
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// the position in `trail` where each decision level starts.
+    trail_lim: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    activity_inc: f64,
+    /// unassigned variables by activity, possibly with outdated entries.
+    order: BinaryHeap<(u64, Var)>,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    model: Vec<bool>,
+    /// false once the clauses are unsatisfiable without assumptions.
+    consistent: bool,
+}
+
+const ACTIVITY_DECAY: f64 = 0.95;
+const RESTART_UNIT: usize = 100;
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver {
+            activity_inc: 1.0,
+            consistent: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn new_var(&mut self) -> Var {
+        let var = self.assigns.len();
+        self.assigns.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        self.seen.push(false);
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.order.push((0, var));
+        var
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.assigns[lit.var()].map(|b| b == lit.is_positive())
+    }
+
+    fn level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    /// adds the disjunction of the literals; returns false if the clauses became unsatisfiable.
+    pub fn add_clause(&mut self, lits: &[Lit]) -> bool {
+        if !self.consistent {
+            return false;
+        }
+        self.backtrack(0);
+        let mut clause: Vec<Lit> = vec![];
+        for lit in lits.iter() {
+            match self.value(*lit) {
+                Some(true) => return true,
+                Some(false) => {}
+                None if clause.contains(&!*lit) => return true,
+                None if !clause.contains(lit) => clause.push(*lit),
+                None => {}
+            }
+        }
+        match clause.len() {
+            0 => self.consistent = false,
+            1 => {
+                self.enqueue(clause[0], None);
+                self.consistent = self.propagate().is_none();
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+        self.consistent
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].0].push(index);
+        self.watches[clause[1].0].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(lit.is_positive());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// unit propagation; returns a falsified clause on conflict.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let watching = std::mem::take(&mut self.watches[false_lit.0]);
+            let mut kept = vec![];
+            let mut conflict = None;
+            for (i, index) in watching.iter().enumerate() {
+                if conflict.is_some() {
+                    kept.extend_from_slice(&watching[i..]);
+                    break;
+                }
+                let clause = &mut self.clauses[*index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.assigns[first.var()].map(|b| b == first.is_positive()) == Some(true) {
+                    kept.push(*index);
+                    continue;
+                }
+                let assigns = &self.assigns;
+                let replacement = clause[2..].iter().position(|lit| {
+                    assigns[lit.var()].map(|b| b == lit.is_positive()) != Some(false)
+                });
+                match replacement {
+                    Some(k) => {
+                        clause.swap(1, k + 2);
+                        let watch = clause[1];
+                        self.watches[watch.0].push(*index);
+                    }
+                    None => {
+                        kept.push(*index);
+                        if self.value(first) == Some(false) {
+                            conflict = Some(*index);
+                        } else {
+                            self.enqueue(first, Some(*index));
+                        }
+                    }
+                }
+            }
+            self.watches[false_lit.0].extend(kept);
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump(&mut self, var: Var) {
+        self.activity[var] += self.activity_inc;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.activity_inc *= 1e-100;
+            self.order = (0..self.assigns.len())
+                .filter(|var| self.assigns[*var].is_none())
+                .map(|var| (self.activity[var].to_bits(), var))
+                .collect();
+        }
+        if self.assigns[var].is_none() {
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+    }
+
+    /// the first-UIP clause learnt from the conflict, asserting literal first,
+    /// and the level to backtrack to.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut index = self.trail.len();
+        let mut implied: Option<Lit> = None;
+        loop {
+            let skip = usize::from(implied.is_some());
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if !self.seen[var] && self.levels[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump(var);
+                    if self.levels[var] == self.level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var()] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            clause = self.reasons[lit.var()].unwrap();
+        }
+        learnt[0] = !implied.unwrap();
+        for lit in learnt[1..].iter() {
+            self.seen[lit.var()] = false;
+        }
+        let mut back_level = 0;
+        if learnt.len() > 1 {
+            let highest = (1..learnt.len())
+                .max_by_key(|k| self.levels[learnt[*k].var()])
+                .unwrap();
+            learnt.swap(1, highest);
+            back_level = self.levels[learnt[1].var()];
+        }
+        self.activity_inc /= ACTIVITY_DECAY;
+        (learnt, back_level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for lit in self.trail.drain(start..).rev() {
+            let var = lit.var();
+            self.assigns[var] = None;
+            self.phases[var] = lit.is_positive();
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+        self.trail_lim.truncate(level);
+        self.propagated = start;
+    }
+
+    fn decide(&mut self) -> Option<Lit> {
+        while let Some((_, var)) = self.order.pop() {
+            if self.assigns[var].is_none() {
+                return Some(Lit::new(var, self.phases[var]));
+            }
+        }
+        None
+    }
+
+    /// whether the clauses are satisfiable.
+    pub fn solve(&mut self) -> bool {
+        self.solve_with(&[])
+    }
+
+    /// whether the clauses are satisfiable with the assumptions true. a satisfying
+    /// assignment is then available through `model_value`.
+    pub fn solve_with(&mut self, assumptions: &[Lit]) -> bool {
+        if !self.consistent {
+            return false;
+        }
+        self.backtrack(0);
+        let mut conflicts = 0;
+        let mut restart = 1;
+        let mut limit = luby(restart) * RESTART_UNIT;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    self.consistent = false;
+                    return false;
+                }
+                conflicts += 1;
+                let (learnt, back_level) = self.analyze(conflict);
+                self.backtrack(back_level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let index = self.attach(learnt);
+                    self.enqueue(asserting, Some(index));
+                }
+                continue;
+            }
+            if conflicts >= limit {
+                conflicts = 0;
+                restart += 1;
+                limit = luby(restart) * RESTART_UNIT;
+                self.backtrack(0);
+                continue;
+            }
+            let next = if self.level() < assumptions.len() {
+                let assumption = assumptions[self.level()];
+                match self.value(assumption) {
+                    Some(false) => {
+                        self.backtrack(0);
+                        return false;
+                    }
+                    // an empty decision level keeps levels aligned with assumptions
+                    Some(true) => None,
+                    None => Some(assumption),
+                }
+            } else {
+                match self.decide() {
+                    Some(lit) => Some(lit),
+                    None => {
+                        self.model = self.assigns.iter().map(|b| b.unwrap()).collect();
+                        self.backtrack(0);
+                        return true;
+                    }
+                }
+            };
+            self.trail_lim.push(self.trail.len());
+            if let Some(lit) = next {
+                self.enqueue(lit, None);
+            }
+        }
+    }
+
+    /// the value of the variable in the assignment found by the last successful `solve`.
+    pub fn model_value(&self, var: Var) -> bool {
+        self.model.get(var).copied().unwrap_or(false)
+    }
+}
+
+/// the `i`-th term of the Luby sequence 1 1 2 1 1 2 4 ..., from 1.
+fn luby(i: usize) -> usize {
+    let mut k = 1;
+    while (1 << k) - 1 < i {
+        k += 1;
+    }
+    if (1 << k) - 1 == i {
+        1 << (k - 1)
+    } else {
+        luby(i - (1 << (k - 1)) + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lits(solver: &mut Solver, n: usize) -> Vec<Lit> {
+        (0..n).map(|_| Lit::new(solver.new_var(), true)).collect()
+    }
+
+    #[test]
+    fn pigeons_do_not_fit_in_fewer_holes() {
+        // pigeon `i` in hole `j` is `p[2 * i + j]`
+        let mut solver = Solver::new();
+        let p = lits(&mut solver, 6);
+        for i in 0..3 {
+            solver.add_clause(&[p[2 * i], p[2 * i + 1]]);
+        }
+        for j in 0..2 {
+            for i in 0..3 {
+                for k in i + 1..3 {
+                    solver.add_clause(&[!p[2 * i + j], !p[2 * k + j]]);
+                }
+            }
+        }
+        assert!(!solver.solve());
+    }
+
+    #[test]
+    fn models_satisfy_the_clauses_and_assumptions() {
+        let mut solver = Solver::new();
+        let x = lits(&mut solver, 3);
+        // x0 -> x1, x1 -> x2, and not all three
+        solver.add_clause(&[!x[0], x[1]]);
+        solver.add_clause(&[!x[1], x[2]]);
+        solver.add_clause(&[!x[0], !x[1], !x[2]]);
+        assert!(solver.solve_with(&[x[1]]));
+        assert!(!solver.model_value(x[0].var()));
+        assert!(solver.model_value(x[2].var()));
+        assert!(!solver.solve_with(&[x[0]]));
+        // a failed assumption leaves the clauses satisfiable
+        assert!(solver.solve());
+    }
+}
@@ -0,0 +1,993 @@
+use std::collections::HashMap;
+
+use crate::{
+    bdd::{Bdd, Node, Var},
+    formula::{Formula, Prop},
+    lts::{self, STUTTER},
+    mark::Deadlock,
+    process::{Label, Location},
+};
+
+/// An expression over the variables of a `Model`, on unsigned integers of `Model::width` bits.
+/// arithmetic wraps around, and booleans are 1 and 0: any non-zero value is true.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(u64),
+    /// the variable of the model at the index.
+    Var(usize),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Le(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// if the first is true the second, else the third.
+    Ite(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// the value of the expression for the values of the variables.
+    pub fn eval(&self, values: &[u64], width: usize) -> u64 {
+        use Expr::*;
+        let mask = |v: u64| {
+            if width >= 64 {
+                v
+            } else {
+                v & ((1 << width) - 1)
+            }
+        };
+        let eval = |e: &Expr| e.eval(values, width);
+        mask(match self {
+            Const(c) => *c,
+            Var(i) => values[*i],
+            Add(a, b) => eval(a).wrapping_add(eval(b)),
+            Sub(a, b) => eval(a).wrapping_sub(eval(b)),
+            Mul(a, b) => eval(a).wrapping_mul(eval(b)),
+            Eq(a, b) => (eval(a) == eval(b)) as u64,
+            Lt(a, b) => (eval(a) < eval(b)) as u64,
+            Le(a, b) => (eval(a) <= eval(b)) as u64,
+            Not(a) => (eval(a) == 0) as u64,
+            And(a, b) => (eval(a) != 0 && eval(b) != 0) as u64,
+            Or(a, b) => (eval(a) != 0 || eval(b) != 0) as u64,
+            Ite(c, a, b) => {
+                if eval(c) != 0 {
+                    eval(a)
+                } else {
+                    eval(b)
+                }
+            }
+        })
+    }
+
+    /// the expression with the variables replaced by the expressions they are assigned,
+    /// as the weakest precondition of a predicate through the assignments. the truncation of
+    /// the assigned values to the width of the variables is not taken into account.
+    pub fn substitute(&self, updates: &[(usize, Expr)]) -> Expr {
+        use Expr::*;
+        let sub = |e: &Expr| Box::new(e.substitute(updates));
+        match self {
+            Const(c) => Const(*c),
+            Var(i) => match updates.iter().find(|(var, _)| var == i) {
+                Some((_, expr)) => expr.clone(),
+                None => Var(*i),
+            },
+            Add(a, b) => Add(sub(a), sub(b)),
+            Sub(a, b) => Sub(sub(a), sub(b)),
+            Mul(a, b) => Mul(sub(a), sub(b)),
+            Eq(a, b) => Eq(sub(a), sub(b)),
+            Lt(a, b) => Lt(sub(a), sub(b)),
+            Le(a, b) => Le(sub(a), sub(b)),
+            Not(a) => Not(sub(a)),
+            And(a, b) => And(sub(a), sub(b)),
+            Or(a, b) => Or(sub(a), sub(b)),
+            Ite(c, a, b) => Ite(sub(c), sub(a), sub(b)),
+        }
+    }
+
+    fn vars(&self, acc: &mut Vec<usize>) {
+        use Expr::*;
+        match self {
+            Const(_) => {}
+            Var(i) => acc.push(*i),
+            Not(a) => a.vars(acc),
+            Add(a, b)
+            | Sub(a, b)
+            | Mul(a, b)
+            | Eq(a, b)
+            | Lt(a, b)
+            | Le(a, b)
+            | And(a, b)
+            | Or(a, b) => {
+                a.vars(acc);
+                b.vars(acc);
+            }
+            Ite(c, a, b) => {
+                c.vars(acc);
+                a.vars(acc);
+                b.vars(acc);
+            }
+        }
+    }
+}
+
+/// A variable of a `Model`, an unsigned integer of `bits` bits.
+#[derive(Debug, Clone)]
+pub struct VarDecl {
+    pub name: String,
+    pub bits: usize,
+    pub init: u64,
+}
+
+/// The symbolic counterpart of `process::Trans`: the guard and the action are expressions,
+/// the action assigning its expressions to the variables all at once.
+#[derive(Debug, Clone)]
+pub struct Trans {
+    pub label: Label,
+    pub dst: Location,
+    pub guard: Expr,
+    pub updates: Vec<(usize, Expr)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecUnit {
+    pub src: Location,
+    pub transs: Vec<Trans>,
+}
+
+pub type Process = Vec<ExecUnit>;
+
+/// A system of processes over bounded integer variables, described symbolically.
+/// a variable assigned a value too wide for it keeps its low bits.
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub vars: Vec<VarDecl>,
+    pub processes: Vec<Process>,
+}
+
+impl Model {
+    /// the width of the values of the expressions: one bit more than the widest variable.
+    pub fn width(&self) -> usize {
+        self.vars.iter().map(|var| var.bits).max().unwrap_or(0) + 1
+    }
+
+    /// the locations of each process, the initial one first.
+    pub fn locations(&self) -> Vec<Vec<Location>> {
+        self.processes
+            .iter()
+            .map(|process| {
+                let mut locations: Vec<Location> = vec![];
+                let dsts = process
+                    .iter()
+                    .flat_map(|exec| exec.transs.iter().map(|t| &t.dst));
+                for location in process.iter().map(|exec| &exec.src).chain(dsts) {
+                    if !locations.contains(location) {
+                        locations.push(location.clone());
+                    }
+                }
+                locations
+            })
+            .collect()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for var in self.vars.iter() {
+            if var.bits == 0 || var.bits > 62 {
+                return Err(format!("variable {} must have 1 to 62 bits", var.name));
+            }
+            if var.init >> var.bits != 0 {
+                return Err(format!("initial value of {} does not fit", var.name));
+            }
+        }
+        for (i, process) in self.processes.iter().enumerate() {
+            if process.is_empty() {
+                return Err(format!("wrong process {}: no transition", i));
+            }
+            for trans in process.iter().flat_map(|exec| exec.transs.iter()) {
+                lts::check_label(&trans.label)?;
+                let mut used = vec![];
+                trans.guard.vars(&mut used);
+                for (var, expr) in trans.updates.iter() {
+                    if trans.updates.iter().filter(|(v, _)| v == var).count() > 1 {
+                        return Err(format!("variable {} updated twice by {}", var, trans.label));
+                    }
+                    used.push(*var);
+                    expr.vars(&mut used);
+                }
+                if let Some(var) = used.iter().find(|var| **var >= self.vars.len()) {
+                    return Err(format!("unknown variable {} in {}", var, trans.label));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A boolean algebra to translate expressions into, bit by bit.
+pub trait Logic {
+    type Bit: Copy + Eq;
+    fn constant(&mut self, value: bool) -> Self::Bit;
+    fn not(&mut self, a: Self::Bit) -> Self::Bit;
+    fn and(&mut self, a: Self::Bit, b: Self::Bit) -> Self::Bit;
+    fn or(&mut self, a: Self::Bit, b: Self::Bit) -> Self::Bit;
+    fn xor(&mut self, a: Self::Bit, b: Self::Bit) -> Self::Bit;
+    fn ite(&mut self, c: Self::Bit, a: Self::Bit, b: Self::Bit) -> Self::Bit;
+}
+
+impl Logic for Bdd {
+    type Bit = Node;
+    fn constant(&mut self, value: bool) -> Node {
+        if value {
+            Bdd::TRUE
+        } else {
+            Bdd::FALSE
+        }
+    }
+    fn not(&mut self, a: Node) -> Node {
+        Bdd::not(self, a)
+    }
+    fn and(&mut self, a: Node, b: Node) -> Node {
+        Bdd::and(self, a, b)
+    }
+    fn or(&mut self, a: Node, b: Node) -> Node {
+        Bdd::or(self, a, b)
+    }
+    fn xor(&mut self, a: Node, b: Node) -> Node {
+        Bdd::xor(self, a, b)
+    }
+    fn ite(&mut self, c: Node, a: Node, b: Node) -> Node {
+        Bdd::ite(self, c, a, b)
+    }
+}
+
+/// the bits of the value of `expr`, least significant first, given those of the variables.
+pub(crate) fn blast<L: Logic>(
+    logic: &mut L,
+    expr: &Expr,
+    vars: &[Vec<L::Bit>],
+    width: usize,
+) -> Vec<L::Bit> {
+    use Expr::*;
+    let zero = logic.constant(false);
+    // a boolean as a word
+    let boolean = |bit: L::Bit| {
+        let mut word = vec![zero; width];
+        word[0] = bit;
+        word
+    };
+    match expr {
+        Const(c) => (0..width)
+            .map(|i| logic.constant(i < 64 && (c >> i) & 1 == 1))
+            .collect(),
+        Var(i) => (0..width)
+            .map(|j| vars[*i].get(j).copied().unwrap_or(zero))
+            .collect(),
+        Add(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            add(logic, &a, &b, zero)
+        }
+        Sub(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            let not_b: Vec<L::Bit> = b.iter().map(|bit| logic.not(*bit)).collect();
+            let one = logic.constant(true);
+            add(logic, &a, &not_b, one)
+        }
+        Mul(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            let mut product = vec![zero; width];
+            for (shift, b_bit) in b.iter().enumerate() {
+                let partial: Vec<L::Bit> = (0..width)
+                    .map(|i| {
+                        if i < shift {
+                            zero
+                        } else {
+                            logic.and(a[i - shift], *b_bit)
+                        }
+                    })
+                    .collect();
+                product = add(logic, &product, &partial, zero);
+            }
+            product
+        }
+        Eq(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            let bit = equal(logic, &a, &b);
+            boolean(bit)
+        }
+        Lt(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            let bit = less(logic, &a, &b);
+            boolean(bit)
+        }
+        Le(a, b) => {
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            let greater = less(logic, &b, &a);
+            let bit = logic.not(greater);
+            boolean(bit)
+        }
+        Not(a) => {
+            let a = truth(logic, a, vars, width);
+            let bit = logic.not(a);
+            boolean(bit)
+        }
+        And(a, b) => {
+            let (a, b) = (truth(logic, a, vars, width), truth(logic, b, vars, width));
+            let bit = logic.and(a, b);
+            boolean(bit)
+        }
+        Or(a, b) => {
+            let (a, b) = (truth(logic, a, vars, width), truth(logic, b, vars, width));
+            let bit = logic.or(a, b);
+            boolean(bit)
+        }
+        Ite(c, a, b) => {
+            let c = truth(logic, c, vars, width);
+            let (a, b) = (blast(logic, a, vars, width), blast(logic, b, vars, width));
+            a.iter()
+                .zip(b.iter())
+                .map(|(x, y)| logic.ite(c, *x, *y))
+                .collect()
+        }
+    }
+}
+
+/// whether the value of `expr` is non-zero.
+pub(crate) fn truth<L: Logic>(
+    logic: &mut L,
+    expr: &Expr,
+    vars: &[Vec<L::Bit>],
+    width: usize,
+) -> L::Bit {
+    let word = blast(logic, expr, vars, width);
+    let mut bit = logic.constant(false);
+    for b in word {
+        bit = logic.or(bit, b);
+    }
+    bit
+}
+
+fn add<L: Logic>(logic: &mut L, a: &[L::Bit], b: &[L::Bit], carry: L::Bit) -> Vec<L::Bit> {
+    let mut carry = carry;
+    let mut sum = vec![];
+    for (x, y) in a.iter().zip(b.iter()) {
+        let half = logic.xor(*x, *y);
+        sum.push(logic.xor(half, carry));
+        let both = logic.and(*x, *y);
+        let propagated = logic.and(half, carry);
+        carry = logic.or(both, propagated);
+    }
+    sum
+}
+
+pub(crate) fn equal<L: Logic>(logic: &mut L, a: &[L::Bit], b: &[L::Bit]) -> L::Bit {
+    let mut bit = logic.constant(true);
+    for (x, y) in a.iter().zip(b.iter()) {
+        let differ = logic.xor(*x, *y);
+        let same = logic.not(differ);
+        bit = logic.and(bit, same);
+    }
+    bit
+}
+
+fn less<L: Logic>(logic: &mut L, a: &[L::Bit], b: &[L::Bit]) -> L::Bit {
+    // from the least significant bit up, the more significant bits decide
+    let mut lt = logic.constant(false);
+    for (x, y) in a.iter().zip(b.iter()) {
+        let not_x = logic.not(*x);
+        let smaller = logic.and(not_x, *y);
+        let differ = logic.xor(*x, *y);
+        let same = logic.not(differ);
+        let kept = logic.and(same, lt);
+        lt = logic.or(smaller, kept);
+    }
+    lt
+}
+
+/// the bits needed to number `n` values.
+pub(crate) fn bits_for(n: usize) -> usize {
+    let mut bits = 1;
+    while (1 << bits) < n {
+        bits += 1;
+    }
+    bits
+}
+
+/// The model encoded with BDDs: a state is a valuation of the bits of the variables followed
+/// by those of the location of each process, numbered in `Model::locations`.
+pub struct Symbolic {
+    bdd: Bdd,
+    width: usize,
+    locations: Vec<Vec<Location>>,
+    /// the bits of the current and of the next value of each variable then location.
+    current: Vec<Vec<Node>>,
+    next: Vec<Vec<Node>>,
+    current_vars: Vec<bool>,
+    next_vars: Vec<bool>,
+    to_next: HashMap<Var, Var>,
+    to_current: HashMap<Var, Var>,
+    /// the relation of each transition, between the current and the next states.
+    transs: Vec<(Label, Node)>,
+    relation: Node,
+    init: Node,
+    reachable: Node,
+}
+
+impl Symbolic {
+    /// encodes the model and computes its reachable states.
+    pub fn new(model: &Model) -> Result<Symbolic, String> {
+        model.validate()?;
+        let width = model.width();
+        let locations = model.locations();
+        let mut bdd = Bdd::new();
+        let slot_bits: Vec<usize> = model
+            .vars
+            .iter()
+            .map(|var| var.bits)
+            .chain(locations.iter().map(|locs| bits_for(locs.len())))
+            .collect();
+        // current and next bits interleaved
+        let mut current = vec![];
+        let mut next = vec![];
+        let mut level = 0;
+        for bits in slot_bits.iter() {
+            let mut cur = vec![];
+            let mut nxt = vec![];
+            for _ in 0..*bits {
+                cur.push(bdd.var(level));
+                nxt.push(bdd.var(level + 1));
+                level += 2;
+            }
+            current.push(cur);
+            next.push(nxt);
+        }
+        let current_vars: Vec<bool> = (0..level).map(|v| v % 2 == 0).collect();
+        let next_vars: Vec<bool> = (0..level).map(|v| v % 2 == 1).collect();
+        let to_next = (0..level).step_by(2).map(|v| (v, v + 1)).collect();
+        let to_current = (0..level).step_by(2).map(|v| (v + 1, v)).collect();
+
+        let mut symbolic = Symbolic {
+            bdd,
+            width,
+            locations,
+            current,
+            next,
+            current_vars,
+            next_vars,
+            to_next,
+            to_current,
+            transs: vec![],
+            relation: Bdd::FALSE,
+            init: Bdd::TRUE,
+            reachable: Bdd::FALSE,
+        };
+
+        let n_vars = model.vars.len();
+        let mut init = Bdd::TRUE;
+        for (i, var) in model.vars.iter().enumerate() {
+            let value = symbolic.value(i, var.init, false);
+            init = symbolic.bdd.and(init, value);
+        }
+        for p in 0..model.processes.len() {
+            let value = symbolic.value(n_vars + p, 0, false);
+            init = symbolic.bdd.and(init, value);
+        }
+        symbolic.init = init;
+
+        for (p, process) in model.processes.iter().enumerate() {
+            for exec in process.iter() {
+                for trans in exec.transs.iter() {
+                    let relation = symbolic.trans_relation(model, p, &exec.src, trans);
+                    symbolic.relation = symbolic.bdd.or(symbolic.relation, relation);
+                    symbolic.transs.push((trans.label.clone(), relation));
+                }
+            }
+        }
+
+        let mut reachable = init;
+        loop {
+            let image = symbolic.post(reachable);
+            let next = symbolic.bdd.or(reachable, image);
+            if next == reachable {
+                break;
+            }
+            reachable = next;
+        }
+        symbolic.reachable = reachable;
+        Ok(symbolic)
+    }
+
+    /// the slot holds the value, in the current or the next state.
+    fn value(&mut self, slot: usize, value: u64, next: bool) -> Node {
+        let bits = if next {
+            self.next[slot].clone()
+        } else {
+            self.current[slot].clone()
+        };
+        let mut node = Bdd::TRUE;
+        for (j, bit) in bits.iter().enumerate() {
+            let literal = if (value >> j) & 1 == 1 {
+                *bit
+            } else {
+                self.bdd.not(*bit)
+            };
+            node = self.bdd.and(node, literal);
+        }
+        node
+    }
+
+    fn location_code(&self, process: usize, location: &Location) -> Option<u64> {
+        self.locations[process]
+            .iter()
+            .position(|l| l == location)
+            .map(|code| code as u64)
+    }
+
+    fn trans_relation(
+        &mut self,
+        model: &Model,
+        process: usize,
+        src: &Location,
+        trans: &Trans,
+    ) -> Node {
+        let n_vars = model.vars.len();
+        let loc_slot = n_vars + process;
+        let src_code = self.location_code(process, src).unwrap();
+        let dst_code = self.location_code(process, &trans.dst).unwrap();
+        let vars = self.current[..n_vars].to_vec();
+
+        let at_src = self.value(loc_slot, src_code, false);
+        let guard = truth(&mut self.bdd, &trans.guard, &vars, self.width);
+        let to_dst = self.value(loc_slot, dst_code, true);
+        let mut relation = self.bdd.and(at_src, guard);
+        relation = self.bdd.and(relation, to_dst);
+        for slot in 0..self.current.len() {
+            if slot == loc_slot {
+                continue;
+            }
+            let next_bits = self.next[slot].clone();
+            let assigned = match trans.updates.iter().find(|(var, _)| *var == slot) {
+                Some((_, expr)) => blast(&mut self.bdd, expr, &vars, self.width),
+                None => self.current[slot].clone(),
+            };
+            let same = equal(&mut self.bdd, &next_bits, &assigned[..next_bits.len()]);
+            relation = self.bdd.and(relation, same);
+        }
+        relation
+    }
+
+    /// the successors of the states.
+    fn post(&mut self, states: Node) -> Node {
+        let image = self
+            .bdd
+            .and_exists(self.relation, states, &self.current_vars.clone());
+        self.bdd.rename(image, &self.to_current.clone())
+    }
+
+    /// the states with a successor by `relation` in `states`.
+    fn pre(&mut self, relation: Node, states: Node) -> Node {
+        let primed = self.bdd.rename(states, &self.to_next.clone());
+        let pre = self
+            .bdd
+            .and_exists(relation, primed, &self.next_vars.clone());
+        self.bdd.and(pre, self.reachable)
+    }
+
+    pub fn reachable(&self) -> Node {
+        self.reachable
+    }
+
+    /// the number of states in the set.
+    pub fn count(&self, states: Node) -> f64 {
+        let levels = self.current_vars.len();
+        // the set does not depend on the next state bits
+        self.bdd.count(states, levels) / 2f64.powi((levels / 2) as i32)
+    }
+
+    /// Whether `spec` holds in the initial state, with the default `Deadlock::Fixpoint`
+    /// semantics of `mark::make_marks`. propositions are translated by `prop_expr` into
+    /// expressions.
+    pub fn check(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        spec: &Formula,
+    ) -> Result<bool, String> {
+        self.check_with(prop_expr, spec, Deadlock::Fixpoint)
+    }
+
+    /// like `check`, treating the terminal states as `mark::make_marks_with` does.
+    pub fn check_with(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        spec: &Formula,
+        deadlock: Deadlock,
+    ) -> Result<bool, String> {
+        let states = self.states_with(prop_expr, spec, deadlock)?;
+        Ok(self.bdd.and(states, self.init) != Bdd::FALSE)
+    }
+
+    /// the reachable states where `spec` holds, with `Deadlock::Fixpoint` semantics.
+    pub fn states(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        spec: &Formula,
+    ) -> Result<Node, String> {
+        self.states_with(prop_expr, spec, Deadlock::Fixpoint)
+    }
+
+    /// the reachable states where `spec` holds, with the deadlock semantics.
+    pub fn states_with(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        spec: &Formula,
+        deadlock: Deadlock,
+    ) -> Result<Node, String> {
+        let live = self.pre(self.relation, Bdd::TRUE);
+        let not_live = self.bdd.not(live);
+        let dead = self.bdd.and(not_live, self.reachable);
+        let loops = match deadlock {
+            Deadlock::Reject if dead != Bdd::FALSE => {
+                return Err("a reachable state has no successor".to_string())
+            }
+            Deadlock::Stutter => {
+                let mut same = dead;
+                for slot in 0..self.current.len() {
+                    let (current, next) = (self.current[slot].clone(), self.next[slot].clone());
+                    let equal = equal(&mut self.bdd, &next, &current);
+                    same = self.bdd.and(same, equal);
+                }
+                same
+            }
+            _ => Bdd::FALSE,
+        };
+        let steps = Steps {
+            dead,
+            loops,
+            deadlock,
+        };
+        self.eval(prop_expr, spec, &steps)
+    }
+
+    fn eval(
+        &mut self,
+        prop_expr: fn(&Prop) -> Option<Expr>,
+        spec: &Formula,
+        steps: &Steps,
+    ) -> Result<Node, String> {
+        use Formula::*;
+        let reachable = self.reachable;
+        Ok(match spec {
+            True => reachable,
+            False => Bdd::FALSE,
+            Prop(p) => {
+                let expr = prop_expr(p).ok_or_else(|| format!("unknown proposition {}", p))?;
+                let n_vars = self.current.len() - self.locations.len();
+                let vars = self.current[..n_vars].to_vec();
+                let holds = truth(&mut self.bdd, &expr, &vars, self.width);
+                self.bdd.and(holds, reachable)
+            }
+            At(process, location) => {
+                if *process >= self.locations.len() {
+                    return Err(format!("no process {}", process));
+                }
+                let slot = self.current.len() - self.locations.len() + process;
+                match self.location_code(*process, location) {
+                    Some(code) => {
+                        let at = self.value(slot, code, false);
+                        self.bdd.and(at, reachable)
+                    }
+                    None => Bdd::FALSE,
+                }
+            }
+            Deadlock => steps.dead,
+            Enabled(label) => {
+                let relation = self.labelled(|l| l == label);
+                self.pre(relation, Bdd::TRUE)
+            }
+            Not(f) => {
+                let f = self.eval(prop_expr, f, steps)?;
+                let not_f = self.bdd.not(f);
+                self.bdd.and(not_f, reachable)
+            }
+            And(f, g) => {
+                let (f, g) = (
+                    self.eval(prop_expr, f, steps)?,
+                    self.eval(prop_expr, g, steps)?,
+                );
+                self.bdd.and(f, g)
+            }
+            Or(f, g) => {
+                let (f, g) = (
+                    self.eval(prop_expr, f, steps)?,
+                    self.eval(prop_expr, g, steps)?,
+                );
+                self.bdd.or(f, g)
+            }
+            Impl(f, g) => {
+                let (f, g) = (
+                    self.eval(prop_expr, f, steps)?,
+                    self.eval(prop_expr, g, steps)?,
+                );
+                let not_f = self.bdd.not(f);
+                let holds = self.bdd.or(not_f, g);
+                self.bdd.and(holds, reachable)
+            }
+            EX(f) => {
+                let f = self.eval(prop_expr, f, steps)?;
+                let relation = self.bdd.or(self.relation, steps.loops);
+                self.pre(relation, f)
+            }
+            Diamond(actions, f) => {
+                let f = self.eval(prop_expr, f, steps)?;
+                let mut relation = self.labelled(|l| actions.contains(l));
+                if actions.contains(&Label::new(STUTTER)) {
+                    relation = self.bdd.or(relation, steps.loops);
+                }
+                self.pre(relation, f)
+            }
+            EU(f, g) => {
+                let (f, g) = (
+                    self.eval(prop_expr, f, steps)?,
+                    self.eval(prop_expr, g, steps)?,
+                );
+                // least fixpoint: Z = g or (f and EX Z)
+                let mut z = g;
+                loop {
+                    let pre = self.pre(self.relation, z);
+                    let step = self.bdd.and(f, pre);
+                    let next = self.bdd.or(g, step);
+                    if next == z {
+                        break z;
+                    }
+                    z = next;
+                }
+            }
+            EG(f) => {
+                let f = self.eval(prop_expr, f, steps)?;
+                // a path may stop at a terminal state unless under `Deadlock::Fixpoint`
+                let stop = match steps.deadlock {
+                    self::Deadlock::Fixpoint => Bdd::FALSE,
+                    _ => steps.dead,
+                };
+                // greatest fixpoint: Z = f and (EX Z or stop)
+                let mut z = f;
+                loop {
+                    let pre = self.pre(self.relation, z);
+                    let go_on = self.bdd.or(pre, stop);
+                    let next = self.bdd.and(f, go_on);
+                    if next == z {
+                        break z;
+                    }
+                    z = next;
+                }
+            }
+            _ => return Err(format!("{} is not supported by the symbolic checker", spec)),
+        })
+    }
+
+    /// the union of the relations of the transitions with a label satisfying `pred`.
+    fn labelled(&mut self, pred: impl Fn(&Label) -> bool) -> Node {
+        let mut relation = Bdd::FALSE;
+        for (label, trans) in self.transs.clone() {
+            if pred(&label) {
+                relation = self.bdd.or(relation, trans);
+            }
+        }
+        relation
+    }
+}
+
+/// the terminal states, and the stuttering loops added to them under `Deadlock::Stutter`.
+struct Steps {
+    dead: Node,
+    loops: Node,
+    deadlock: Deadlock,
+}
+
+use std::fmt;
+impl fmt::Display for Expr {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Expr::*;
+        match self {
+            Const(ref c) => write!(fmt, "{}", c),
+            Var(ref i) => write!(fmt, "v{}", i),
+            Add(ref a, ref b) => write!(fmt, "(+ {} {})", a, b),
+            Sub(ref a, ref b) => write!(fmt, "(- {} {})", a, b),
+            Mul(ref a, ref b) => write!(fmt, "(* {} {})", a, b),
+            Eq(ref a, ref b) => write!(fmt, "(= {} {})", a, b),
+            Lt(ref a, ref b) => write!(fmt, "(< {} {})", a, b),
+            Le(ref a, ref b) => write!(fmt, "(<= {} {})", a, b),
+            Not(ref a) => write!(fmt, "(not {})", a),
+            And(ref a, ref b) => write!(fmt, "(and {} {})", a, b),
+            Or(ref a, ref b) => write!(fmt, "(or {} {})", a, b),
+            Ite(ref c, ref a, ref b) => write!(fmt, "(ite {} {} {})", c, a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// a single process incrementing `x` of `bits` bits up to `max`, where it deadlocks.
+    pub(crate) fn counter(bits: usize, max: u64) -> Model {
+        let x = || Box::new(Expr::Var(0));
+        Model {
+            vars: vec![VarDecl {
+                name: "x".to_string(),
+                bits,
+                init: 0,
+            }],
+            processes: vec![vec![ExecUnit {
+                src: Location::new("L"),
+                transs: vec![Trans {
+                    label: Label::new("inc"),
+                    dst: Location::new("L"),
+                    guard: Expr::Lt(x(), Box::new(Expr::Const(max))),
+                    updates: vec![(0, Expr::Add(x(), Box::new(Expr::Const(1))))],
+                }],
+            }]],
+        }
+    }
+
+    /// reads the propositions `x<N` of the counters.
+    pub(crate) fn prop_expr(p: &Prop) -> Option<Expr> {
+        let bound = p.strip_prefix("x<")?.parse().ok()?;
+        Some(Expr::Lt(
+            Box::new(Expr::Var(0)),
+            Box::new(Expr::Const(bound)),
+        ))
+    }
+
+    pub(crate) fn below(bound: u64) -> Formula {
+        Formula::Prop(format!("x<{}", bound))
+    }
+
+    /// the counter of 2 bits up to 3, with process 1 moving from `P` to `Q` once `x` is 2.
+    fn model() -> Model {
+        let mut model = counter(2, 3);
+        model.processes.push(vec![ExecUnit {
+            src: Location::new("P"),
+            transs: vec![Trans {
+                label: Label::new("go"),
+                dst: Location::new("Q"),
+                guard: Expr::Eq(Box::new(Expr::Var(0)), Box::new(Expr::Const(2))),
+                updates: vec![],
+            }],
+        }]);
+        model
+    }
+
+    #[test]
+    fn reachable_states_and_formulas() {
+        let mut symbolic = Symbolic::new(&model()).unwrap();
+        assert_eq!(symbolic.count(symbolic.reachable()), 6.0);
+        let at_q = || Box::new(Formula::At(1, Location::new("Q")));
+        let deadlocks = symbolic.states(prop_expr, &Formula::Deadlock).unwrap();
+        assert_eq!(symbolic.count(deadlocks), 2.0);
+        let x_below_1 = symbolic.states(prop_expr, &below(1)).unwrap();
+        assert_eq!(symbolic.count(x_below_1), 1.0);
+        let ef_q = Formula::EU(Box::new(Formula::True), at_q());
+        assert_eq!(symbolic.check(prop_expr, &ef_q), Ok(true));
+        // the path incrementing `x` to 3 ends at a deadlock in `P`, which only a maximal
+        // path may do
+        let eg_p = Formula::EG(Box::new(Formula::Not(at_q())));
+        assert_eq!(symbolic.check(prop_expr, &eg_p), Ok(false));
+        assert_eq!(
+            symbolic.check_with(prop_expr, &eg_p, Deadlock::Finite),
+            Ok(true)
+        );
+        assert_eq!(
+            symbolic.check_with(prop_expr, &eg_p, Deadlock::Reject),
+            Err("a reachable state has no successor".to_string())
+        );
+        let ex = Formula::EX(Box::new(below(1)));
+        assert_eq!(symbolic.check(prop_expr, &ex), Ok(false));
+        let go = Formula::Enabled(Label::new("go"));
+        let go = symbolic.states(prop_expr, &go).unwrap();
+        assert_eq!(symbolic.count(go), 1.0);
+        let unknown = symbolic.check(prop_expr, &Formula::Prop("y".to_string()));
+        assert_eq!(unknown, Err("unknown proposition y".to_string()));
+    }
+
+    #[test]
+    fn invalid_models_are_rejected() {
+        let mut wide = model();
+        wide.vars[0].init = 4;
+        assert_eq!(
+            Symbolic::new(&wide).err(),
+            Some("initial value of x does not fit".to_string())
+        );
+        let mut unknown = model();
+        unknown.processes[1][0].transs[0].updates = vec![(1, Expr::Const(0))];
+        assert_eq!(
+            Symbolic::new(&unknown).err(),
+            Some("unknown variable 1 in go".to_string())
+        );
+    }
+
+    #[test]
+    fn backends_agree() {
+        use crate::{formula::Actions, lts::Lts, mark, process};
+        // the explicit counterpart of `model`
+        let inc = process::Trans {
+            label: Label::new("inc"),
+            dst: Location::new("L"),
+            guard: |x: &u64| *x < 3,
+            action: |x| x + 1,
+            clock_guard: vec![],
+            resets: vec![],
+        };
+        let go = process::Trans {
+            label: Label::new("go"),
+            dst: Location::new("Q"),
+            guard: |x: &u64| *x == 2,
+            action: |x| x,
+            clock_guard: vec![],
+            resets: vec![],
+        };
+        let processes = vec![
+            vec![process::ExecUnit {
+                src: Location::new("L"),
+                invariant: vec![],
+                transs: vec![inc],
+            }],
+            vec![
+                process::ExecUnit {
+                    src: Location::new("P"),
+                    invariant: vec![],
+                    transs: vec![go],
+                },
+                process::ExecUnit {
+                    src: Location::new("Q"),
+                    invariant: vec![],
+                    transs: vec![],
+                },
+            ],
+        ];
+        let mut lts = Lts::concurrent_composition(0, processes).unwrap();
+        let prop_valuate = |p: &Prop, x: &u64| p == "x<1" && *x < 1;
+        let mut symbolic = Symbolic::new(&model()).unwrap();
+        assert_eq!(symbolic.count(symbolic.reachable()), lts.0.len() as f64);
+
+        let b = Box::new;
+        let at_q = || b(Formula::At(1, Location::new("Q")));
+        let formulas = [
+            Formula::EG(b(Formula::Not(at_q()))),
+            Formula::Not(b(Formula::EG(b(Formula::Not(at_q()))))),
+            Formula::EG(b(Formula::True)),
+            Formula::EX(b(Formula::True)),
+            Formula::EX(b(Formula::Deadlock)),
+            Formula::Diamond(Actions::Any, b(Formula::Deadlock)),
+            Formula::EU(b(Formula::Not(at_q())), b(Formula::Deadlock)),
+            Formula::EX(b(below(1))),
+        ];
+        for deadlock in [Deadlock::Fixpoint, Deadlock::Finite, Deadlock::Stutter] {
+            let options = mark::Options {
+                deadlock,
+                ..Default::default()
+            };
+            for f in formulas.iter() {
+                let marks = mark::make_marks_with(&mut lts, prop_valuate, f.clone(), &options);
+                let marks = marks.unwrap();
+                let explicit = (0..lts.0.len()).filter(|id| marks.holds_at(*id)).count();
+                let states = symbolic.states_with(prop_expr, f, deadlock).unwrap();
+                assert_eq!(
+                    symbolic.count(states),
+                    explicit as f64,
+                    "{} under {:?}",
+                    f,
+                    deadlock
+                );
+                assert_eq!(
+                    symbolic.check_with(prop_expr, f, deadlock),
+                    Ok(marks.holds_at(0)),
+                    "{} under {:?}",
+                    f,
+                    deadlock
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+//! Timed processes, explored as zone graphs.
+//!
+//! the clocks are read by the clock guards and resets of `process::Trans` and by the
+//! invariants of `process::ExecUnit`, which the untimed compositions reject. each state of
+//! a zone graph holds the zone of its clock valuations in `lts::State::zone`.
+
+use std::fmt;
+use std::hash::Hash;
+
+use crate::{
+    lts::{self, Lts, State, Trace},
+    process::{ExecUnit, Label, Location, Process},
+};
+
+/// index of a clock, counted from 0.
+pub type Clock = usize;
+
+/// A clock constraint: the clock compared to an integer constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClockConstraint {
+    Le(Clock, i64),
+    Lt(Clock, i64),
+    Ge(Clock, i64),
+    Gt(Clock, i64),
+}
+
+impl ClockConstraint {
+    fn clock(&self) -> Clock {
+        match *self {
+            ClockConstraint::Le(x, _)
+            | ClockConstraint::Lt(x, _)
+            | ClockConstraint::Ge(x, _)
+            | ClockConstraint::Gt(x, _) => x,
+        }
+    }
+
+    fn constant(&self) -> i64 {
+        match *self {
+            ClockConstraint::Le(_, c)
+            | ClockConstraint::Lt(_, c)
+            | ClockConstraint::Ge(_, c)
+            | ClockConstraint::Gt(_, c) => c,
+        }
+    }
+}
+
+/// A bound `x - y ≺ c` of a difference bound matrix,
+/// encoded as `2c + 1` when `≺` is `<=` and as `2c` when it is `<`.
+type Bound = i64;
+
+const INFINITY: Bound = i64::MAX;
+
+fn bound(c: i64, strict: bool) -> Bound {
+    2 * c + if strict { 0 } else { 1 }
+}
+
+fn add(a: Bound, b: Bound) -> Bound {
+    if a == INFINITY || b == INFINITY {
+        INFINITY
+    } else {
+        bound((a >> 1) + (b >> 1), (a & b & 1) == 0)
+    }
+}
+
+/// A zone: a convex set of clock valuations, as a canonical difference bound matrix.
+/// row and column 0 stand for the constant 0, clock `x` for row and column `x + 1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dbm {
+    dim: usize,
+    bounds: Vec<Bound>,
+}
+
+impl Dbm {
+    /// the zone where every clock is 0.
+    pub fn zero(clocks: usize) -> Dbm {
+        let dim = clocks + 1;
+        Dbm {
+            dim,
+            bounds: vec![bound(0, false); dim * dim],
+        }
+    }
+
+    fn get(&self, i: usize, j: usize) -> Bound {
+        self.bounds[i * self.dim + j]
+    }
+
+    fn set(&mut self, i: usize, j: usize, b: Bound) {
+        self.bounds[i * self.dim + j] = b;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..self.dim).any(|i| self.get(i, i) < bound(0, false))
+    }
+
+    /// restores the canonical form, where each bound is the tightest implied one.
+    fn close(&mut self) {
+        for k in 0..self.dim {
+            for i in 0..self.dim {
+                for j in 0..self.dim {
+                    let through = add(self.get(i, k), self.get(k, j));
+                    if through < self.get(i, j) {
+                        self.set(i, j, through);
+                    }
+                }
+            }
+        }
+    }
+
+    /// intersects the zone with the constraint.
+    pub fn constrain(&mut self, constraint: &ClockConstraint) {
+        let x = constraint.clock() + 1;
+        let (i, j, b) = match *constraint {
+            ClockConstraint::Le(_, c) => (x, 0, bound(c, false)),
+            ClockConstraint::Lt(_, c) => (x, 0, bound(c, true)),
+            ClockConstraint::Ge(_, c) => (0, x, bound(-c, false)),
+            ClockConstraint::Gt(_, c) => (0, x, bound(-c, true)),
+        };
+        if b < self.get(i, j) {
+            self.set(i, j, b);
+            self.close();
+        }
+    }
+
+    /// whether some valuation of the zone satisfies the constraint.
+    pub fn may(&self, constraint: &ClockConstraint) -> bool {
+        let mut zone = self.clone();
+        zone.constrain(constraint);
+        !zone.is_empty()
+    }
+
+    /// lets any amount of time pass.
+    pub fn up(&mut self) {
+        for i in 1..self.dim {
+            self.set(i, 0, INFINITY);
+        }
+    }
+
+    pub fn reset(&mut self, clock: Clock) {
+        let x = clock + 1;
+        for j in 0..self.dim {
+            let (to, from) = (self.get(0, j), self.get(j, 0));
+            self.set(x, j, to);
+            self.set(j, x, from);
+        }
+        self.set(x, x, bound(0, false));
+    }
+
+    /// forgets the bounds beyond the greatest constant each clock is compared to,
+    /// which keeps the zone graph finite.
+    fn extrapolate(&mut self, max: &[i64]) {
+        let max = |i: usize| if i == 0 { 0 } else { max[i - 1] };
+        for i in 0..self.dim {
+            for j in 0..self.dim {
+                if i == j {
+                    continue;
+                }
+                let b = self.get(i, j);
+                if b != INFINITY && b > bound(max(i), false) {
+                    self.set(i, j, INFINITY);
+                } else if b < bound(-max(j), true) {
+                    self.set(i, j, bound(-max(j), true));
+                }
+            }
+        }
+        self.close();
+    }
+}
+
+/// Builds the zone graph of the timed processes: each state holds the zone of the clock
+/// valuations reachable in it, time having passed as far as the invariants allow.
+pub fn zone_graph<T: Clone + Hash + Eq>(
+    vars: T,
+    clocks: usize,
+    processes: Vec<Process<T>>,
+) -> Result<Lts<T>, String> {
+    explore(vars, clocks, &processes, None)
+}
+
+/// Checks whether a state satisfying `target` is reachable with some valuation of the
+/// clocks, returning the zone graph with a shortest path to such a state, if any.
+pub fn reachable<T: Clone + Hash + Eq>(
+    vars: T,
+    clocks: usize,
+    processes: Vec<Process<T>>,
+    target: fn(&State<T>) -> bool,
+) -> Result<(Lts<T>, Option<Trace>), String> {
+    let lts = explore(vars, clocks, &processes, None)?;
+    let found = lts.find_states(|_, trans| target(&trans.state));
+    let trace = found
+        .first()
+        .map(|state_id| lts::shortest_trace(&lts, *state_id));
+    Ok((lts, trace))
+}
+
+/// location of the observer of `bounded_response` while it awaits a response.
+const AWAITING: &str = "awaiting";
+
+/// location of the observer of `bounded_response` otherwise.
+const IDLE: &str = "idle";
+
+/// Checks that whenever `trigger` holds, `response` holds within `deadline` time units.
+///
+/// the zone graph is explored with an observer, whose location follows those of the
+/// processes, and an extra clock reset when the observer starts awaiting a response.
+/// returns the explored graph with a path to a state where time may pass beyond the
+/// deadline while still awaiting, if any.
+pub fn bounded_response<T: Clone + Hash + Eq>(
+    vars: T,
+    clocks: usize,
+    processes: Vec<Process<T>>,
+    trigger: fn(&State<T>) -> bool,
+    response: fn(&State<T>) -> bool,
+    deadline: i64,
+) -> Result<(Lts<T>, Option<Trace>), String> {
+    let observer = Observer {
+        clock: clocks,
+        trigger,
+        response,
+        deadline,
+    };
+    let lts = explore(vars, clocks, &processes, Some(&observer))?;
+    let late = ClockConstraint::Gt(clocks, deadline);
+    let awaiting = Location::new(AWAITING);
+    let violations = lts.find_states(|_, trans| {
+        let zone = trans.state.zone.as_ref().unwrap();
+        trans.state.locations.last() == Some(&awaiting) && zone.may(&late)
+    });
+    let trace = violations
+        .first()
+        .map(|state_id| lts::shortest_trace(&lts, *state_id));
+    Ok((lts, trace))
+}
+
+struct Observer<T> {
+    /// the extra clock, following the clocks of the processes.
+    clock: Clock,
+    trigger: fn(&State<T>) -> bool,
+    response: fn(&State<T>) -> bool,
+    deadline: i64,
+}
+
+impl<T: Clone> Observer<T> {
+    /// moves the observer, last of the locations, on entering the state of the processes:
+    /// resets its clock when it starts awaiting a response.
+    fn observe(&self, vars: &T, locations: &mut [Location], zone: &mut Dbm) {
+        let (observer, locations) = locations.split_last_mut().unwrap();
+        let state = State {
+            vars: vars.clone(),
+            locations: locations.to_vec(),
+            zone: None,
+        };
+        if (self.response)(&state) {
+            *observer = Location::new(IDLE);
+        } else if observer.0 == IDLE && (self.trigger)(&state) {
+            zone.reset(self.clock);
+            *observer = Location::new(AWAITING);
+        }
+    }
+}
+
+fn explore<T: Clone + Hash + Eq>(
+    vars: T,
+    clocks: usize,
+    processes: &[Process<T>],
+    observer: Option<&Observer<T>>,
+) -> Result<Lts<T>, String> {
+    fn exec_unit<'p, T>(process: &'p Process<T>, location: &Location) -> Option<&'p ExecUnit<T>> {
+        process.iter().find(|exec| exec.src == *location)
+    }
+    // enters the locations: applies their invariants and lets time pass
+    let enter = |locations: &[Location], zone: &mut Dbm, max: &[i64]| {
+        let invariants: Vec<_> = processes
+            .iter()
+            .zip(locations.iter())
+            .filter_map(|(process, location)| exec_unit(process, location))
+            .flat_map(|exec| exec.invariant.iter())
+            .collect();
+        for constraint in invariants.iter() {
+            zone.constrain(constraint);
+        }
+        zone.up();
+        for constraint in invariants.iter() {
+            zone.constrain(constraint);
+        }
+        zone.extrapolate(max);
+    };
+
+    let extra = if observer.is_some() { 1 } else { 0 };
+    let mut max = vec![0; clocks + extra];
+    for exec in processes.iter().flatten() {
+        for trans in exec.transs.iter() {
+            lts::check_label(&trans.label)?;
+        }
+        let constraints = exec.invariant.iter().chain(
+            exec.transs
+                .iter()
+                .flat_map(|trans| trans.clock_guard.iter()),
+        );
+        for constraint in constraints {
+            let clock = constraint.clock();
+            if clock >= clocks {
+                return Err(format!("clock {} out of {} clocks", clock, clocks));
+            }
+            max[clock] = max[clock].max(constraint.constant().abs());
+        }
+        for clock in exec.transs.iter().flat_map(|trans| trans.resets.iter()) {
+            if *clock >= clocks {
+                return Err(format!("clock {} out of {} clocks", clock, clocks));
+            }
+        }
+    }
+    if let Some(observer) = observer {
+        max[observer.clock] = observer.deadline;
+    }
+
+    let mut init = lts::init_state(vars, processes)?;
+    let mut zone = Dbm::zero(clocks + extra);
+    if let Some(observer) = observer {
+        init.locations.push(Location::new(IDLE));
+        observer.observe(&init.vars, &mut init.locations, &mut zone);
+    }
+    enter(&init.locations, &mut zone, &max);
+    if zone.is_empty() {
+        return Err("the initial invariants do not hold".to_string());
+    }
+    init.zone = Some(zone);
+
+    let next_func = |state: &State<T>| -> Vec<(Label, usize, f64, State<T>)> {
+        let mut next = vec![];
+        for (i, (process, location)) in processes.iter().zip(state.locations.iter()).enumerate() {
+            let exec = match exec_unit(process, location) {
+                Some(exec) => exec,
+                None => continue,
+            };
+            for trans in exec.transs.iter() {
+                if !(trans.guard)(&state.vars) {
+                    continue;
+                }
+                let mut zone = state.zone.clone().unwrap();
+                for constraint in trans.clock_guard.iter() {
+                    zone.constrain(constraint);
+                }
+                if zone.is_empty() {
+                    continue;
+                }
+                for clock in trans.resets.iter() {
+                    zone.reset(*clock);
+                }
+                let mut locations = state.locations.clone();
+                locations[i] = trans.dst.clone();
+                let vars = (trans.action)(state.vars.clone());
+                if let Some(observer) = observer {
+                    observer.observe(&vars, &mut locations, &mut zone);
+                }
+                enter(&locations, &mut zone, &max);
+                if zone.is_empty() {
+                    continue;
+                }
+                let dst_state = State {
+                    vars,
+                    locations,
+                    zone: Some(zone),
+                };
+                next.push((trans.label.clone(), i, 1.0, dst_state));
+            }
+        }
+        next
+    };
+    Ok(lts::bfs(init, next_func))
+}
+
+impl fmt::Display for Dbm {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = |i: usize| format!("x{}", i - 1);
+        let show = |b: Bound| format!("{}{}", if b & 1 == 1 { "<=" } else { "<" }, b >> 1);
+        let mut constraints = vec![];
+        for i in 0..self.dim {
+            for j in 0..self.dim {
+                let b = self.get(i, j);
+                if i == j || b == INFINITY || (i == 0 && b == bound(0, false)) {
+                    continue;
+                }
+                constraints.push(match (i, j) {
+                    (0, j) => {
+                        let op = if b & 1 == 1 { ">=" } else { ">" };
+                        format!("{}{}{}", name(j), op, -(b >> 1))
+                    }
+                    (i, 0) => format!("{}{}", name(i), show(b)),
+                    (i, j) => format!("{}-{}{}", name(i), name(j), show(b)),
+                });
+            }
+        }
+        write!(fmt, "{}", constraints.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::Trans;
+
+    fn unit(src: &str, invariant: Vec<ClockConstraint>, transs: Vec<Trans<i32>>) -> ExecUnit<i32> {
+        ExecUnit {
+            src: Location::new(src),
+            invariant,
+            transs,
+        }
+    }
+
+    fn trans(label: &str, dst: &str, clock_guard: Vec<ClockConstraint>) -> Trans<i32> {
+        Trans {
+            label: Label::new(label),
+            dst: Location::new(dst),
+            guard: |_| true,
+            action: |x| x,
+            clock_guard,
+            resets: vec![0],
+        }
+    }
+
+    /// requests at any time, then answers after 1 to 3 time units: the timeout after 3
+    /// is never taken.
+    fn server() -> Process<i32> {
+        vec![
+            unit("idle", vec![], vec![trans("req", "busy", vec![])]),
+            unit(
+                "busy",
+                vec![ClockConstraint::Le(0, 3)],
+                vec![
+                    trans("ack", "idle", vec![ClockConstraint::Ge(0, 1)]),
+                    trans("timeout", "late", vec![ClockConstraint::Gt(0, 3)]),
+                ],
+            ),
+        ]
+    }
+
+    fn at(location: &str, state: &State<i32>) -> bool {
+        state.locations[0] == Location::new(location)
+    }
+
+    #[test]
+    fn zones() {
+        let mut zone = Dbm::zero(2);
+        assert!(!zone.may(&ClockConstraint::Gt(0, 0)));
+        zone.up();
+        zone.constrain(&ClockConstraint::Le(0, 5));
+        assert!(zone.may(&ClockConstraint::Ge(1, 5)));
+        assert!(!zone.may(&ClockConstraint::Gt(1, 5)));
+        zone.reset(0);
+        zone.constrain(&ClockConstraint::Ge(1, 2));
+        assert!(!zone.may(&ClockConstraint::Gt(0, 0)));
+        zone.constrain(&ClockConstraint::Lt(1, 2));
+        assert!(zone.is_empty());
+    }
+
+    #[test]
+    fn reachability_follows_the_clocks() {
+        let (lts, trace) = reachable(0, 1, vec![server()], |state| at("busy", state)).unwrap();
+        let trace = trace.unwrap();
+        assert_eq!(trace.to_string(), "0 -req-> 1");
+        let zone = lts.0[trace.last()].state.zone.as_ref().unwrap();
+        assert!(zone.may(&ClockConstraint::Ge(0, 3)));
+        assert!(!zone.may(&ClockConstraint::Gt(0, 3)));
+        let (_, trace) = reachable(0, 1, vec![server()], |state| at("late", state)).unwrap();
+        assert_eq!(trace, None);
+        let untimed = Lts::concurrent_composition(0, vec![server()]);
+        assert!(untimed.is_err());
+    }
+
+    #[test]
+    fn bounded_response_within_the_invariant() {
+        let busy = |state: &State<i32>| state.locations[0] == Location::new("busy");
+        let idle = |state: &State<i32>| state.locations[0] == Location::new("idle");
+        let (_, trace) = bounded_response(0, 1, vec![server()], busy, idle, 3).unwrap();
+        assert_eq!(trace, None);
+        assert_eq!(zone_graph(0, 1, vec![server()]).unwrap().0.len(), 2);
+        let (_, trace) = bounded_response(0, 1, vec![server()], busy, idle, 2).unwrap();
+        assert_eq!(trace.unwrap().to_string(), "0 -req-> 1");
+    }
+}
@@ -1,13 +1,23 @@
-use crate::{lts::Lts, mark::Marks};
+use crate::{lts::Lts, mark::Marks, query::Annotation};
 
 use std::fmt::Display;
 use std::hash::Hash;
 pub fn lts<T: Eq + Hash + Display>(filename: &str, lts: &Lts<T>, marks: Marks) {
+    lts_annotated(filename, lts, marks, &[])
+}
+
+/// like `lts`, also printing the value of each annotation in the states.
+pub fn lts_annotated<T: Eq + Hash + Display>(
+    filename: &str,
+    lts: &Lts<T>,
+    marks: Marks,
+    annotations: &[Annotation],
+) {
     use std::fs;
     use std::io::{BufWriter, Write};
     let mut f = BufWriter::new(fs::File::create(filename).expect("cannot create output file."));
 
-    f.write(b"digraph {{").unwrap();
+    f.write_all(b"digraph {{").unwrap();
 
     // emit states
     for (state_id, trans) in lts.0.iter().enumerate() {
@@ -16,34 +26,44 @@ pub fn lts<T: Eq + Hash + Display>(filename: &str, lts: &Lts<T>, marks: Marks) {
         for loc in trans.state.locations.iter() {
             f.write_fmt(format_args!("{}", loc)).unwrap();
         }
-        f.write_fmt(format_args!("\\n{}", &trans.state.vars))
+        f.write_fmt(format_args!("\\n{}", trans.state.vars))
             .unwrap();
+        if let Some(zone) = &trans.state.zone {
+            f.write_fmt(format_args!("\\n[{}]", zone)).unwrap();
+        }
         for (i, formula) in marks.subformulas.iter() {
             if marks.marks.get(state_id).unwrap().is_marked(*i) {
                 f.write_fmt(format_args!("\\n{}", formula)).unwrap();
             }
         }
-        f.write(b"\",").unwrap();
+        for annotation in annotations.iter() {
+            match annotation.values[state_id] {
+                Some(value) => f.write_fmt(format_args!("\\n{}={}", annotation.name, value)),
+                None => f.write_fmt(format_args!("\\n{}=-", annotation.name)),
+            }
+            .unwrap();
+        }
+        f.write_all(b"\",").unwrap();
         if marks
             .marks
             .get(state_id)
             .unwrap()
             .is_marked(marks.subformulas.len() - 1)
         {
-            f.write(b"style=filled,fillcolor=palegreen").unwrap();
+            f.write_all(b"style=filled,fillcolor=palegreen").unwrap();
         }
-        f.write(b"];\n").unwrap();
+        f.write_all(b"];\n").unwrap();
     }
 
     // emit transitions
     for (src_id, trans) in lts.0.iter().enumerate() {
-        for (label, dst_id) in trans.dst.iter() {
+        for step in trans.steps.iter() {
             f.write_fmt(format_args!(
                 "{} -> {} [label=\"{}\"];\n",
-                src_id, dst_id, label
+                src_id, step.dst, step.label
             ))
             .unwrap()
         }
     }
-    f.write(b"}}").unwrap();
+    f.write_all(b"}}").unwrap();
 }